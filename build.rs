@@ -0,0 +1,30 @@
+use std::process::Command;
+
+/// Exposes the commit being built as `GIT_HASH` for `/version`. A `GIT_HASH` set in the
+/// environment wins, for builds outside of a checkout, e.g. in a container.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+
+    // HEAD only names the branch, its ref changes on every commit
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD") {
+        if let Some(branch_ref) = head.trim().strip_prefix("ref: ") {
+            println!("cargo:rerun-if-changed=.git/{}", branch_ref);
+        }
+    }
+
+    if std::env::var("GIT_HASH").is_ok() {
+        return;
+    }
+
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+
+    if let Some(git_hash) = git_hash {
+        println!("cargo:rustc-env=GIT_HASH={}", git_hash.trim());
+    }
+}
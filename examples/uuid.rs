@@ -3,5 +3,5 @@ use uuid::Uuid;
 fn main() {
     let uuid = Uuid::new_v4();
 
-    println!("{}", uuid.to_string());
+    println!("{}", uuid);
 }
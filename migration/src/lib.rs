@@ -5,6 +5,22 @@ mod m20241203_073620_create_task_table;
 mod m20241205_064650_create_user_profile_table;
 mod m20241231_054040_create_label_table;
 mod m20241231_055020_create_task_label_map_table;
+mod m20250110_091500_create_refresh_token_table;
+mod m20250112_140000_create_revoked_token_table;
+mod m20250112_141000_add_token_valid_after_to_user;
+mod m20250115_100000_add_role_to_user;
+mod m20250120_083000_normalise_task_status_and_priority;
+mod m20250122_110000_add_parent_id_to_task;
+mod m20250125_090000_create_task_dependency_table;
+mod m20250201_093000_create_task_series_table;
+mod m20250205_100000_create_task_comment_table;
+mod m20250210_120000_create_attachment_table;
+mod m20250215_090000_add_task_search;
+mod m20250220_090000_create_login_attempt_table;
+mod m20250220_091000_add_lockout_to_user;
+mod m20250225_090000_create_personal_access_token_table;
+mod m20250301_090000_create_password_reset_token_table;
+mod m20250301_091000_create_email_outbox_table;
 
 pub struct Migrator;
 
@@ -17,6 +33,22 @@ impl MigratorTrait for Migrator {
             Box::new(m20241205_064650_create_user_profile_table::Migration),
             Box::new(m20241231_054040_create_label_table::Migration),
             Box::new(m20241231_055020_create_task_label_map_table::Migration),
+            Box::new(m20250110_091500_create_refresh_token_table::Migration),
+            Box::new(m20250112_140000_create_revoked_token_table::Migration),
+            Box::new(m20250112_141000_add_token_valid_after_to_user::Migration),
+            Box::new(m20250115_100000_add_role_to_user::Migration),
+            Box::new(m20250120_083000_normalise_task_status_and_priority::Migration),
+            Box::new(m20250122_110000_add_parent_id_to_task::Migration),
+            Box::new(m20250125_090000_create_task_dependency_table::Migration),
+            Box::new(m20250201_093000_create_task_series_table::Migration),
+            Box::new(m20250205_100000_create_task_comment_table::Migration),
+            Box::new(m20250210_120000_create_attachment_table::Migration),
+            Box::new(m20250215_090000_add_task_search::Migration),
+            Box::new(m20250220_090000_create_login_attempt_table::Migration),
+            Box::new(m20250220_091000_add_lockout_to_user::Migration),
+            Box::new(m20250225_090000_create_personal_access_token_table::Migration),
+            Box::new(m20250301_090000_create_password_reset_token_table::Migration),
+            Box::new(m20250301_091000_create_email_outbox_table::Migration),
         ]
     }
 }
@@ -0,0 +1,72 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RefreshToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RefreshToken::Id))
+                    .col(string(RefreshToken::Jti).unique_key())
+                    .col(string(RefreshToken::Family))
+                    .col(integer(RefreshToken::UserId))
+                    .col(timestamp_with_time_zone(RefreshToken::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(RefreshToken::UsedAt))
+                    .col(timestamp_with_time_zone_null(RefreshToken::RevokedAt))
+                    .col(
+                        timestamp_with_time_zone(RefreshToken::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-refresh-token-user_id")
+                            .from(RefreshToken::Table, RefreshToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-refresh-token-family")
+                    .table(RefreshToken::Table)
+                    .col(RefreshToken::Family)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RefreshToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RefreshToken {
+    Table,
+    Id,
+    Jti,
+    Family,
+    UserId,
+    ExpiresAt,
+    UsedAt,
+    RevokedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
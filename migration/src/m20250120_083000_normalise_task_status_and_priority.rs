@@ -0,0 +1,73 @@
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // status and priority used to be free-form, map the spellings clients came up
+        // with onto the values of `TaskStatus` and `TaskPriority`
+        db.execute_unprepared(
+            "UPDATE task SET status = CASE
+                WHEN LOWER(TRIM(status)) IN ('done', 'finished', 'complete', 'completed', 'closed')
+                    THEN 'done'
+                WHEN REPLACE(REPLACE(LOWER(TRIM(status)), ' ', '_'), '-', '_')
+                    IN ('in_progress', 'inprogress', 'doing', 'started', 'active')
+                    THEN 'in_progress'
+                WHEN REPLACE(REPLACE(LOWER(TRIM(status)), ' ', '_'), '-', '_')
+                    IN ('reopened', 're_opened', 'reopen')
+                    THEN 'reopened'
+                ELSE 'pending'
+            END
+            WHERE status NOT IN ('pending', 'in_progress', 'done', 'reopened')",
+        )
+        .await?;
+
+        db.execute_unprepared(
+            "UPDATE task SET priority = CASE
+                WHEN LOWER(TRIM(priority)) IN ('urgent', 'critical', 'blocker', 'highest')
+                    THEN 'urgent'
+                WHEN LOWER(TRIM(priority)) IN ('high', 'important')
+                    THEN 'high'
+                WHEN LOWER(TRIM(priority)) IN ('medium', 'normal', 'moderate')
+                    THEN 'medium'
+                ELSE 'low'
+            END
+            WHERE priority NOT IN ('low', 'medium', 'high', 'urgent')",
+        )
+        .await?;
+
+        // SQLite can't add constraints to an existing table, there the enums are only
+        // enforced by the application
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            db.execute_unprepared(
+                "ALTER TABLE task
+                    ADD CONSTRAINT task_status_check
+                        CHECK (status IN ('pending', 'in_progress', 'done', 'reopened')),
+                    ADD CONSTRAINT task_priority_check
+                        CHECK (priority IN ('low', 'medium', 'high', 'urgent'))",
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    "ALTER TABLE task
+                        DROP CONSTRAINT IF EXISTS task_status_check,
+                        DROP CONSTRAINT IF EXISTS task_priority_check",
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TaskSeries::Table)
+                    .if_not_exists()
+                    .col(pk_auto(TaskSeries::Id))
+                    .col(integer(TaskSeries::UserId))
+                    .col(string_len(TaskSeries::Frequency, 20))
+                    .col(integer(TaskSeries::Interval).default(1))
+                    .col(string_null(TaskSeries::ByWeekday))
+                    .col(timestamp_with_time_zone(TaskSeries::StartsAt))
+                    .col(timestamp_with_time_zone_null(TaskSeries::Until))
+                    .col(integer_null(TaskSeries::Count))
+                    .col(integer(TaskSeries::OccurrenceCount).default(1))
+                    .col(boolean(TaskSeries::Active).default(true))
+                    .col(
+                        timestamp_with_time_zone(TaskSeries::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(TaskSeries::DateUpdated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-task-series-user_id")
+                            .from(TaskSeries::Table, TaskSeries::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let sqlite = manager.get_database_backend() == DatabaseBackend::Sqlite;
+
+        let mut series_id = integer_null(Task::SeriesId);
+
+        // SQLite can't add a foreign key to an existing table, only a column referencing one
+        if sqlite {
+            series_id.extra("REFERENCES task_series(id) ON DELETE SET NULL ON UPDATE CASCADE");
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Task::Table)
+                    .add_column(series_id)
+                    .to_owned(),
+            )
+            .await?;
+
+        if !sqlite {
+            manager
+                .create_foreign_key(
+                    ForeignKey::create()
+                        .name("fk-task-series_id")
+                        .from(Task::Table, Task::SeriesId)
+                        .to(TaskSeries::Table, TaskSeries::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                        .on_update(ForeignKeyAction::Cascade)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            manager
+                .drop_foreign_key(
+                    ForeignKey::drop()
+                        .name("fk-task-series_id")
+                        .table(Task::Table)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Task::Table)
+                    .drop_column(Task::SeriesId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(TaskSeries::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TaskSeries {
+    Table,
+    Id,
+    UserId,
+    Frequency,
+    Interval,
+    ByWeekday,
+    StartsAt,
+    Until,
+    Count,
+    OccurrenceCount,
+    Active,
+    DateCreated,
+    DateUpdated,
+}
+
+#[derive(DeriveIden)]
+enum Task {
+    Table,
+    SeriesId,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,104 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TaskComment::Table)
+                    .if_not_exists()
+                    .col(pk_auto(TaskComment::Id))
+                    .col(integer(TaskComment::TaskId))
+                    .col(integer(TaskComment::UserId))
+                    .col(text(TaskComment::Body))
+                    .col(boolean(TaskComment::Edited).default(false))
+                    .col(
+                        timestamp_with_time_zone(TaskComment::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(TaskComment::DateUpdated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-task-comment-task_id")
+                            .from(TaskComment::Table, TaskComment::TaskId)
+                            .to(Task::Table, Task::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-task-comment-user_id")
+                            .from(TaskComment::Table, TaskComment::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-task-comment-task_id")
+                    .table(TaskComment::Table)
+                    .col(TaskComment::TaskId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // kept up to date with the comments so task listings don't need to count them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Task::Table)
+                    .add_column(integer(Task::CommentCount).default(0))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Task::Table)
+                    .drop_column(Task::CommentCount)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(TaskComment::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TaskComment {
+    Table,
+    Id,
+    TaskId,
+    UserId,
+    Body,
+    Edited,
+    DateCreated,
+    DateUpdated,
+}
+
+#[derive(DeriveIden)]
+enum Task {
+    Table,
+    Id,
+    CommentCount,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,101 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Attachment::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Attachment::Id))
+                    .col(string_uniq(Attachment::Uuid))
+                    .col(integer(Attachment::TaskId))
+                    .col(integer(Attachment::UserId))
+                    .col(string(Attachment::Filename))
+                    .col(string(Attachment::ContentType))
+                    .col(big_integer(Attachment::Size))
+                    .col(string_len(Attachment::Checksum, 64))
+                    .col(string(Attachment::StorageKey))
+                    .col(
+                        timestamp_with_time_zone(Attachment::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-attachment-task_id")
+                            .from(Attachment::Table, Attachment::TaskId)
+                            .to(Task::Table, Task::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-attachment-user_id")
+                            .from(Attachment::Table, Attachment::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-attachment-task_id")
+                    .table(Attachment::Table)
+                    .col(Attachment::TaskId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // quota checks sum the sizes per user
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-attachment-user_id")
+                    .table(Attachment::Table)
+                    .col(Attachment::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Attachment::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Attachment {
+    Table,
+    Id,
+    Uuid,
+    TaskId,
+    UserId,
+    Filename,
+    ContentType,
+    Size,
+    Checksum,
+    StorageKey,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum Task {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
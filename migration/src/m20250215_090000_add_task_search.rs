@@ -0,0 +1,174 @@
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Full-text search over task titles, descriptions and comments, kept up to date by
+/// triggers. Postgres gets a weighted `tsvector` column with a GIN index, SQLite an
+/// FTS5 table keyed by the task id.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        match manager.get_database_backend() {
+            DatabaseBackend::Postgres => {
+                db.execute_unprepared(
+                    "ALTER TABLE task ADD COLUMN search_vector tsvector;
+
+                    CREATE OR REPLACE FUNCTION task_search_document(
+                        task_title text, task_description text, task_id integer
+                    )
+                    RETURNS tsvector AS $$
+                        SELECT setweight(to_tsvector('english', coalesce(task_title, '')), 'A')
+                            || setweight(to_tsvector('english', coalesce(task_description, '')), 'B')
+                            || setweight(to_tsvector('english', coalesce(
+                                (SELECT string_agg(body, ' ') FROM task_comment
+                                    WHERE task_comment.task_id = task_search_document.task_id),
+                                ''
+                            )), 'C');
+                    $$ LANGUAGE sql STABLE;
+
+                    CREATE OR REPLACE FUNCTION update_task_search_vector()
+                    RETURNS TRIGGER AS $$
+                    BEGIN
+                        NEW.search_vector := task_search_document(NEW.title, NEW.description, NEW.id);
+                        RETURN NEW;
+                    END;
+                    $$ LANGUAGE plpgsql;
+
+                    CREATE TRIGGER set_task_search_vector
+                    BEFORE INSERT OR UPDATE OF title, description ON task
+                    FOR EACH ROW
+                    EXECUTE FUNCTION update_task_search_vector();
+
+                    CREATE OR REPLACE FUNCTION update_task_search_vector_from_comment()
+                    RETURNS TRIGGER AS $$
+                    DECLARE
+                        changed_task_id integer;
+                    BEGIN
+                        IF TG_OP = 'DELETE' THEN
+                            changed_task_id := OLD.task_id;
+                        ELSE
+                            changed_task_id := NEW.task_id;
+                        END IF;
+
+                        UPDATE task SET search_vector = task_search_document(title, description, id)
+                        WHERE id = changed_task_id;
+
+                        RETURN NULL;
+                    END;
+                    $$ LANGUAGE plpgsql;
+
+                    CREATE TRIGGER set_task_search_vector_from_comment
+                    AFTER INSERT OR UPDATE OF body OR DELETE ON task_comment
+                    FOR EACH ROW
+                    EXECUTE FUNCTION update_task_search_vector_from_comment();
+
+                    UPDATE task SET search_vector = task_search_document(title, description, id);
+
+                    CREATE INDEX idx_task_search_vector ON task USING GIN (search_vector);
+                    ",
+                )
+                .await?;
+            }
+            DatabaseBackend::Sqlite => {
+                db.execute_unprepared(
+                    "CREATE VIRTUAL TABLE task_search USING fts5(title, description, comments);
+
+                    INSERT INTO task_search (rowid, title, description, comments)
+                    SELECT id, title, description, coalesce(
+                        (SELECT group_concat(body, ' ') FROM task_comment
+                            WHERE task_comment.task_id = task.id),
+                        ''
+                    )
+                    FROM task;
+
+                    CREATE TRIGGER task_search_insert AFTER INSERT ON task BEGIN
+                        INSERT INTO task_search (rowid, title, description, comments)
+                        VALUES (NEW.id, NEW.title, NEW.description, '');
+                    END;
+
+                    CREATE TRIGGER task_search_update AFTER UPDATE OF title, description ON task
+                    BEGIN
+                        UPDATE task_search SET title = NEW.title, description = NEW.description
+                        WHERE rowid = NEW.id;
+                    END;
+
+                    CREATE TRIGGER task_search_delete AFTER DELETE ON task BEGIN
+                        DELETE FROM task_search WHERE rowid = OLD.id;
+                    END;
+
+                    CREATE TRIGGER task_search_comment_insert AFTER INSERT ON task_comment BEGIN
+                        UPDATE task_search SET comments = coalesce(
+                            (SELECT group_concat(body, ' ') FROM task_comment
+                                WHERE task_id = NEW.task_id),
+                            ''
+                        )
+                        WHERE rowid = NEW.task_id;
+                    END;
+
+                    CREATE TRIGGER task_search_comment_update AFTER UPDATE OF body ON task_comment
+                    BEGIN
+                        UPDATE task_search SET comments = coalesce(
+                            (SELECT group_concat(body, ' ') FROM task_comment
+                                WHERE task_id = NEW.task_id),
+                            ''
+                        )
+                        WHERE rowid = NEW.task_id;
+                    END;
+
+                    CREATE TRIGGER task_search_comment_delete AFTER DELETE ON task_comment BEGIN
+                        UPDATE task_search SET comments = coalesce(
+                            (SELECT group_concat(body, ' ') FROM task_comment
+                                WHERE task_id = OLD.task_id),
+                            ''
+                        )
+                        WHERE rowid = OLD.task_id;
+                    END;
+                    ",
+                )
+                .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        match manager.get_database_backend() {
+            DatabaseBackend::Postgres => {
+                db.execute_unprepared(
+                    "DROP TRIGGER IF EXISTS set_task_search_vector_from_comment ON task_comment;
+                    DROP TRIGGER IF EXISTS set_task_search_vector ON task;
+                    DROP FUNCTION IF EXISTS update_task_search_vector_from_comment();
+                    DROP FUNCTION IF EXISTS update_task_search_vector();
+                    DROP FUNCTION IF EXISTS task_search_document(text, text, integer);
+                    DROP INDEX IF EXISTS idx_task_search_vector;
+                    ALTER TABLE task DROP COLUMN IF EXISTS search_vector;
+                    ",
+                )
+                .await?;
+            }
+            DatabaseBackend::Sqlite => {
+                db.execute_unprepared(
+                    "DROP TRIGGER IF EXISTS task_search_insert;
+                    DROP TRIGGER IF EXISTS task_search_update;
+                    DROP TRIGGER IF EXISTS task_search_delete;
+                    DROP TRIGGER IF EXISTS task_search_comment_insert;
+                    DROP TRIGGER IF EXISTS task_search_comment_update;
+                    DROP TRIGGER IF EXISTS task_search_comment_delete;
+                    DROP TABLE IF EXISTS task_search;
+                    ",
+                )
+                .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginAttempt::Table)
+                    .if_not_exists()
+                    .col(pk_auto(LoginAttempt::Id))
+                    .col(string(LoginAttempt::Username))
+                    .col(integer_null(LoginAttempt::UserId))
+                    .col(string_len_null(LoginAttempt::IpAddress, 45))
+                    .col(string_len_null(LoginAttempt::UserAgent, 512))
+                    .col(boolean(LoginAttempt::Succeeded))
+                    .col(string_len_null(LoginAttempt::FailureReason, 20))
+                    .col(
+                        timestamp_with_time_zone(LoginAttempt::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    // attempts are kept when the user is deleted, they're an audit trail
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-login-attempt-user_id")
+                            .from(LoginAttempt::Table, LoginAttempt::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-login-attempt-username")
+                    .table(LoginAttempt::Table)
+                    .col(LoginAttempt::Username)
+                    .col(LoginAttempt::DateCreated)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-login-attempt-user_id")
+                    .table(LoginAttempt::Table)
+                    .col(LoginAttempt::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginAttempt::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum LoginAttempt {
+    Table,
+    Id,
+    Username,
+    UserId,
+    IpAddress,
+    UserAgent,
+    Succeeded,
+    FailureReason,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
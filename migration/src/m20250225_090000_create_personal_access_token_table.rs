@@ -0,0 +1,83 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PersonalAccessToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(PersonalAccessToken::Id))
+                    .col(integer(PersonalAccessToken::UserId))
+                    .col(string_len(PersonalAccessToken::Name, 100))
+                    .col(string_len(PersonalAccessToken::TokenPrefix, 12))
+                    // SHA-256 of the token, the token itself is only shown once
+                    .col(string_len(PersonalAccessToken::TokenHash, 64).unique_key())
+                    .col(string(PersonalAccessToken::Scopes))
+                    .col(timestamp_with_time_zone_null(
+                        PersonalAccessToken::ExpiresAt,
+                    ))
+                    .col(timestamp_with_time_zone_null(
+                        PersonalAccessToken::LastUsedAt,
+                    ))
+                    .col(timestamp_with_time_zone_null(
+                        PersonalAccessToken::RevokedAt,
+                    ))
+                    .col(
+                        timestamp_with_time_zone(PersonalAccessToken::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-personal-access-token-user_id")
+                            .from(PersonalAccessToken::Table, PersonalAccessToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-personal-access-token-user_id")
+                    .table(PersonalAccessToken::Table)
+                    .col(PersonalAccessToken::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PersonalAccessToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PersonalAccessToken {
+    Table,
+    Id,
+    UserId,
+    Name,
+    TokenPrefix,
+    TokenHash,
+    Scopes,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PasswordResetToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(PasswordResetToken::Id))
+                    .col(integer(PasswordResetToken::UserId))
+                    // SHA-256 of the token, the token itself is only in the email
+                    .col(string_len(PasswordResetToken::TokenHash, 64).unique_key())
+                    .col(timestamp_with_time_zone(PasswordResetToken::ExpiresAt))
+                    .col(timestamp_with_time_zone_null(PasswordResetToken::UsedAt))
+                    .col(
+                        timestamp_with_time_zone(PasswordResetToken::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-password-reset-token-user_id")
+                            .from(PasswordResetToken::Table, PasswordResetToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-password-reset-token-user_id")
+                    .table(PasswordResetToken::Table)
+                    .col(PasswordResetToken::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PasswordResetToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordResetToken {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    UsedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailOutbox::Table)
+                    .if_not_exists()
+                    .col(pk_auto(EmailOutbox::Id))
+                    .col(string(EmailOutbox::Recipient))
+                    .col(string(EmailOutbox::Subject))
+                    .col(text(EmailOutbox::Body))
+                    .col(integer(EmailOutbox::Attempts).default(0))
+                    .col(text_null(EmailOutbox::LastError))
+                    .col(
+                        timestamp_with_time_zone(EmailOutbox::NextAttemptAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        timestamp_with_time_zone(EmailOutbox::DateCreated)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-email-outbox-next_attempt_at")
+                    .table(EmailOutbox::Table)
+                    .col(EmailOutbox::NextAttemptAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EmailOutbox::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EmailOutbox {
+    Table,
+    Id,
+    Recipient,
+    Subject,
+    Body,
+    Attempts,
+    LastError,
+    NextAttemptAt,
+    DateCreated,
+}
@@ -1,4 +1,8 @@
-use axum::{response::IntoResponse, Json};
+use axum::{
+    http::{header, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
 use serde::Serialize;
 use serde_json::{json, Value};
 
@@ -10,10 +14,17 @@ pub enum JsonResponse {
 }
 
 impl JsonResponse {
-    pub fn error(err: impl Serialize, message: Option<String>) -> JsonResponse {
+    pub fn error(
+        code: &str,
+        err: impl Serialize,
+        message: Option<String>,
+        correlation_id: &str,
+    ) -> JsonResponse {
         Self::Error(ErrorResponse {
+            code: code.to_string(),
             error: json!(err),
             message: message.unwrap_or("An error occured.".to_string()),
+            correlation_id: correlation_id.to_string(),
         })
     }
     pub fn data(data: impl Serialize, message: Option<String>) -> JsonResponse {
@@ -37,8 +48,14 @@ impl JsonResponse {
 
 #[derive(Serialize)]
 pub struct ErrorResponse {
+    /// Stable, machine-readable kind of the error, e.g. `not_found` or
+    /// `validation_failed`.
+    pub code: String,
+    /// Details of the error, for `validation_failed` the failed rules of every field.
     pub error: Value,
     pub message: String,
+    /// Quote it when reporting the error, it's attached to the logged details.
+    pub correlation_id: String,
 }
 
 #[derive(Serialize)]
@@ -57,17 +74,28 @@ pub struct ResponseMetadata {
     pub previous_url: Option<String>,
     pub current_url: Option<String>,
     pub next_url: Option<String>,
+    /// Opaque cursors of the neighbouring pages, pass them back as `cursor`.
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
 }
 
 impl ResponseMetadata {
-    pub fn new(count: u64, url: Option<String>) -> Self {
-        Self {
-            count,
-            per_page: 10,
-            total_page: count.div_ceil(10),
-            current_url: url,
-            ..Default::default()
-        }
+    /// The page links as an RFC 8288 `Link` header value.
+    pub fn link_header(&self) -> Option<String> {
+        let links: Vec<String> = [
+            ("first", &self.first_page_url),
+            ("prev", &self.previous_url),
+            ("next", &self.next_url),
+            ("last", &self.last_page_url),
+        ]
+        .into_iter()
+        .filter_map(|(rel, url)| {
+            url.as_ref()
+                .map(|url| format!("<{}>; rel=\"{}\"", url, rel))
+        })
+        .collect();
+
+        (!links.is_empty()).then(|| links.join(", "))
     }
 }
 
@@ -78,16 +106,25 @@ pub struct PaginatedResponse {
     pub message: String,
 }
 
-// error: [{"title": ["Row not found", "hello"]}, {"email": ["Not valid", ""]}]
-//
-//
-
 impl IntoResponse for JsonResponse {
     fn into_response(self) -> axum::response::Response {
         match self {
             JsonResponse::Error(err) => Json(err).into_response(),
             JsonResponse::Data(data) => Json(data).into_response(),
-            JsonResponse::Paginate(paginated_response) => Json(paginated_response).into_response(),
+            JsonResponse::Paginate(paginated_response) => {
+                let link_header = paginated_response
+                    .metadata
+                    .link_header()
+                    .and_then(|links| HeaderValue::from_str(&links).ok());
+
+                let mut response = Json(paginated_response).into_response();
+
+                if let Some(link_header) = link_header {
+                    response.headers_mut().insert(header::LINK, link_header);
+                }
+
+                response
+            }
         }
     }
 }
@@ -1,12 +1,56 @@
-use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenClaims {
+    /// Id of the user, not the email or username, which can change.
     pub sub: String,
     pub iat: usize,
+    /// `iat` to the microsecond, so a token issued in the same second as its user's
+    /// `token_valid_after` is still told apart, see [`TokenClaims::issued_at`].
+    #[serde(rename = "iat_us")]
+    pub iat_micros: i64,
     pub exp: usize,
+    pub jti: String,
+    #[serde(rename = "typ")]
+    pub token_type: TokenType,
+    /// Refresh token family the token was issued in, see `refresh_token.family`.
+    #[serde(rename = "fam")]
+    pub family: String,
+}
+
+impl TokenClaims {
+    pub fn new(subject: &str, token_type: TokenType, family: &str, expire_in_minutes: i64) -> Self {
+        let now = Utc::now();
+
+        Self {
+            sub: subject.to_string(),
+            iat: now.timestamp() as usize,
+            iat_micros: now.timestamp_micros(),
+            exp: (now + Duration::minutes(expire_in_minutes)).timestamp() as usize,
+            jti: uuid::Uuid::new_v4().to_string(),
+            token_type,
+            family: family.to_string(),
+        }
+    }
+
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_micros(self.iat_micros).unwrap_or_default()
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.exp as i64, 0).unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -15,25 +59,52 @@ pub struct UserToken {
     pub refresh_token: Option<String>,
 }
 
-pub async fn create_user_token(subject: &str, expire_in_minutes: i64) -> String {
-    let now = Utc::now();
-    let iat = now.timestamp() as usize;
-    let exp = (now + Duration::minutes(expire_in_minutes)).timestamp() as usize;
-
-    let token_claims = TokenClaims {
-        sub: subject.to_string(),
-        iat,
-        exp,
-    };
-
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT Secret not set.");
-
-    let access_token = encode(
+pub async fn create_user_token(token_claims: &TokenClaims, jwt_secret: &str) -> String {
+    encode(
         &Header::default(),
-        &token_claims,
+        token_claims,
         &EncodingKey::from_secret(jwt_secret.as_ref()),
     )
-    .expect("Cannot encode user token");
+    .expect("Cannot encode user token")
+}
+
+/// Decodes a token and checks that it was issued as `token_type`, so a refresh token
+/// can't be used as a bearer token and vice versa.
+pub fn decode_user_token(
+    token: &str,
+    token_type: TokenType,
+    jwt_secret: &str,
+) -> Result<TokenClaims, AppError> {
+    let token_claims = decode::<TokenClaims>(
+        token,
+        &DecodingKey::from_secret(jwt_secret.as_ref()),
+        &Validation::default(),
+    )
+    .map_err(|_| AppError::Unauthorized("Authentication credentials are invalid.".to_string()))?
+    .claims;
+
+    if token_claims.token_type != token_type {
+        return Err(AppError::Unauthorized(
+            "Authentication credentials are invalid.".to_string(),
+        ));
+    }
+
+    Ok(token_claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn token_type_is_enforced() {
+        let claims = TokenClaims::new("42", TokenType::Refresh, "family", 5);
+        let token = create_user_token(&claims, "test-secret").await;
+
+        assert!(decode_user_token(&token, TokenType::Access, "test-secret").is_err());
 
-    access_token
+        let decoded = decode_user_token(&token, TokenType::Refresh, "test-secret").unwrap();
+        assert_eq!(decoded.jti, claims.jti);
+        assert_eq!(decoded.sub, "42");
+    }
 }
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{
+    prelude::{DateTimeWithTimeZone, Expr},
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+};
+
+use crate::{config::LockoutConfig, models::_entities::user};
+
+/// Failed login bookkeeping of a user, as stored in the `user` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoginFailures {
+    pub count: i32,
+    pub last_failed_at: Option<DateTime<Utc>>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl From<&user::Model> for LoginFailures {
+    fn from(user: &user::Model) -> Self {
+        Self {
+            count: user.failed_login_count,
+            last_failed_at: user.last_failed_login_at.map(Into::into),
+            locked_until: user.locked_until.map(Into::into),
+        }
+    }
+}
+
+impl LoginFailures {
+    pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+        self.locked_until.is_some_and(|until| until > now)
+    }
+}
+
+/// Records a failed login of `user_id` at `now`, locking the account once there are
+/// too many, and returns the failures as they are after it.
+///
+/// The count goes up in a single `UPDATE`, so concurrent failures all count.
+pub async fn record_failure<C>(
+    db: &C,
+    user_id: i32,
+    now: DateTime<Utc>,
+    config: &LockoutConfig,
+) -> Result<LoginFailures, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let window_start: DateTimeWithTimeZone =
+        (now - Duration::minutes(config.failure_window_minutes)).into();
+
+    // the window is counted from the end of a lockout too, so the next failure after
+    // one still makes the lockout longer
+    let in_window = Condition::any()
+        .add(user::Column::LastFailedLoginAt.gte(window_start))
+        .add(user::Column::LockedUntil.gte(window_start));
+
+    let user = user::Entity::update_many()
+        .col_expr(
+            user::Column::FailedLoginCount,
+            Expr::case(in_window, Expr::col(user::Column::FailedLoginCount).add(1))
+                .finally(1)
+                .into(),
+        )
+        .col_expr(
+            user::Column::LastFailedLoginAt,
+            Expr::value(DateTimeWithTimeZone::from(now)),
+        )
+        .filter(user::Column::Id.eq(user_id))
+        .exec_with_returning(db)
+        .await?
+        .pop()
+        .ok_or(DbErr::RecordNotUpdated)?;
+
+    let mut failures = LoginFailures::from(&user);
+
+    if let Some(locked_until) = lockout_until(failures.count, now, config) {
+        let locked_until_tz: DateTimeWithTimeZone = locked_until.into();
+
+        // a concurrent failure may have locked it for longer already
+        user::Entity::update_many()
+            .col_expr(user::Column::LockedUntil, Expr::value(locked_until_tz))
+            .filter(user::Column::Id.eq(user_id))
+            .filter(
+                Condition::any()
+                    .add(user::Column::LockedUntil.is_null())
+                    .add(user::Column::LockedUntil.lt(locked_until_tz)),
+            )
+            .exec(db)
+            .await?;
+
+        failures.locked_until = failures.locked_until.max(Some(locked_until));
+    }
+
+    Ok(failures)
+}
+
+/// When an account with `count` failures in a row, the last at `now`, is unlocked
+/// again, `None` while there are too few to lock it. Every failure past
+/// `max_failed_logins` doubles the lockout, up to `max_lockout_minutes`.
+fn lockout_until(count: i32, now: DateTime<Utc>, config: &LockoutConfig) -> Option<DateTime<Utc>> {
+    (count >= config.max_failed_logins).then(|| {
+        let doublings = (count - config.max_failed_logins).min(20) as u32;
+        let minutes = config
+            .lockout_minutes
+            .saturating_mul(1 << doublings)
+            .min(config.max_lockout_minutes);
+
+        now + Duration::minutes(minutes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LockoutConfig {
+        LockoutConfig {
+            max_failed_logins: 3,
+            failure_window_minutes: 15,
+            lockout_minutes: 10,
+            max_lockout_minutes: 30,
+        }
+    }
+
+    #[test]
+    fn locks_after_repeated_failures_for_longer_each_time() {
+        let now = Utc::now();
+        let minutes = |m: i64| Some(now + Duration::minutes(m));
+
+        assert_eq!(lockout_until(1, now, &config()), None);
+        assert_eq!(lockout_until(2, now, &config()), None);
+        assert_eq!(lockout_until(3, now, &config()), minutes(10));
+
+        // every failure after that doubles it
+        assert_eq!(lockout_until(4, now, &config()), minutes(20));
+
+        // and it's capped
+        assert_eq!(lockout_until(5, now, &config()), minutes(30));
+        assert_eq!(lockout_until(100, now, &config()), minutes(30));
+    }
+
+    #[test]
+    fn locked_until_the_lockout_ends() {
+        let now = Utc::now();
+        let failures = LoginFailures {
+            count: 3,
+            last_failed_at: Some(now),
+            locked_until: lockout_until(3, now, &config()),
+        };
+
+        assert!(failures.is_locked(now + Duration::minutes(9)));
+        assert!(!failures.is_locked(now + Duration::minutes(10)));
+    }
+}
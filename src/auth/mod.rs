@@ -1 +1,7 @@
 pub mod jwt;
+pub mod lockout;
+pub mod password;
+pub mod password_reset;
+pub mod personal_access_token;
+pub mod revocation;
+pub mod scope;
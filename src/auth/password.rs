@@ -0,0 +1,210 @@
+use std::{fmt::Debug, sync::Arc};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, SaltString},
+    Algorithm, Argon2, Params, PasswordHasher as _, PasswordVerifier as _, Version,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::error::AppError;
+
+/// Hashes and verifies user passwords stored in the `user.password` column.
+pub trait PasswordHasher: Debug + Send + Sync {
+    fn hash(&self, password: &str) -> Result<String, AppError>;
+
+    fn verify(&self, hash: &str, password: &str) -> Result<bool, AppError>;
+
+    /// Whether a stored hash should be replaced by a fresh one on the next successful login.
+    fn needs_rehash(&self, hash: &str) -> bool;
+}
+
+/// Runs [`PasswordHasher::hash`] on the blocking thread pool. Argon2 is slow on
+/// purpose and would otherwise stall every request sharing the async worker.
+pub async fn hash_blocking(
+    hasher: &Arc<dyn PasswordHasher>,
+    password: &str,
+) -> Result<String, AppError> {
+    let hasher = hasher.clone();
+    let password = password.to_owned();
+
+    tokio::task::spawn_blocking(move || hasher.hash(&password))
+        .await
+        .map_err(|err| AppError::Internal(err.to_string()))?
+}
+
+/// Runs [`PasswordHasher::verify`] on the blocking thread pool, see [`hash_blocking`].
+pub async fn verify_blocking(
+    hasher: &Arc<dyn PasswordHasher>,
+    hash: &str,
+    password: &str,
+) -> Result<bool, AppError> {
+    let hasher = hasher.clone();
+    let hash = hash.to_owned();
+    let password = password.to_owned();
+
+    tokio::task::spawn_blocking(move || hasher.verify(&hash, &password))
+        .await
+        .map_err(|err| AppError::Internal(err.to_string()))?
+}
+
+/// Argon2id hasher producing PHC strings, e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`.
+///
+/// Hashes created by the old HMAC scheme are still accepted by `verify` and always
+/// reported by `needs_rehash`, so they get upgraded the next time the user logs in.
+#[derive(Debug)]
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(params: Params) -> Self {
+        Self { params }
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl Default for Argon2Hasher {
+    fn default() -> Self {
+        Self::new(Params::default())
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn hash(&self, password: &str) -> Result<String, AppError> {
+        let salt = SaltString::generate(&mut OsRng);
+
+        let password_hash = self
+            .argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|err| AppError::Internal(err.to_string()))?;
+
+        Ok(password_hash.to_string())
+    }
+
+    fn verify(&self, hash: &str, password: &str) -> Result<bool, AppError> {
+        if !is_phc_string(hash) {
+            return LegacyHmacHasher.verify(hash, password);
+        }
+
+        let password_hash =
+            PasswordHash::new(hash).map_err(|err| AppError::Internal(err.to_string()))?;
+
+        Ok(self
+            .argon2()
+            .verify_password(password.as_bytes(), &password_hash)
+            .is_ok())
+    }
+
+    fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(password_hash) = PasswordHash::new(hash) else {
+            return true;
+        };
+
+        if password_hash.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+
+        match Params::try_from(&password_hash) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// The original scheme: hex encoded HMAC-SHA256 of the password with a fixed key.
+///
+/// Only kept around to verify hashes that have not been upgraded yet.
+#[derive(Debug)]
+pub struct LegacyHmacHasher;
+
+impl LegacyHmacHasher {
+    const KEY: &'static [u8] = b"secret_key";
+
+    fn mac(password: &str) -> Hmac<Sha256> {
+        let mut mac: Hmac<Sha256> =
+            Hmac::new_from_slice(Self::KEY).expect("HMAC can take key of any size");
+
+        mac.update(password.as_bytes());
+
+        mac
+    }
+}
+
+impl PasswordHasher for LegacyHmacHasher {
+    fn hash(&self, password: &str) -> Result<String, AppError> {
+        Ok(hex::encode(Self::mac(password).finalize().into_bytes()))
+    }
+
+    fn verify(&self, hash: &str, password: &str) -> Result<bool, AppError> {
+        let code_bytes = hex::decode(hash).map_err(|err| AppError::Internal(err.to_string()))?;
+
+        // `verify_slice` compares in constant time.
+        Ok(Self::mac(password).verify_slice(&code_bytes).is_ok())
+    }
+
+    fn needs_rehash(&self, _hash: &str) -> bool {
+        true
+    }
+}
+
+fn is_phc_string(hash: &str) -> bool {
+    hash.starts_with('$')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hasher() -> Argon2Hasher {
+        // keep the tests fast, the defaults are deliberately expensive
+        Argon2Hasher::new(Params::new(1024, 1, 1, None).unwrap())
+    }
+
+    #[test]
+    fn hash_and_verify() {
+        let hasher = hasher();
+        let hash = hasher.hash("correct horse").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(hasher.verify(&hash, "correct horse").unwrap());
+        assert!(!hasher.verify(&hash, "battery staple").unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn same_password_gets_different_salts() {
+        let hasher = hasher();
+
+        assert_ne!(
+            hasher.hash("password123").unwrap(),
+            hasher.hash("password123").unwrap()
+        );
+    }
+
+    #[test]
+    fn legacy_hashes_verify_and_need_rehash() {
+        let hasher = hasher();
+        let legacy_hash = LegacyHmacHasher.hash("password123").unwrap();
+
+        assert!(hasher.verify(&legacy_hash, "password123").unwrap());
+        assert!(!hasher.verify(&legacy_hash, "password124").unwrap());
+        assert!(hasher.needs_rehash(&legacy_hash));
+    }
+
+    #[test]
+    fn changed_params_need_rehash() {
+        let hash = hasher().hash("password123").unwrap();
+        let stronger = Argon2Hasher::new(Params::new(2048, 2, 1, None).unwrap());
+
+        assert!(stronger.verify(&hash, "password123").unwrap());
+        assert!(stronger.needs_rehash(&hash));
+    }
+}
@@ -0,0 +1,59 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::{mailer::Email, models::_entities::user};
+
+/// A new reset token: the value sent by email, and what's stored of it.
+pub struct GeneratedToken {
+    pub token: String,
+    pub hash: String,
+}
+
+pub fn generate() -> GeneratedToken {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    let token = hex::encode(bytes);
+
+    GeneratedToken {
+        hash: hash(&token),
+        token,
+    }
+}
+
+pub fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// The email with the link to choose a new password, `url` has `{token}` in it.
+pub fn reset_email(user: &user::Model, url: &str, token: &str, expire_minutes: i64) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hi {},\n\n\
+             Someone asked to reset the password of your account {}. Open this link within \
+             {} minutes to choose a new one:\n\n\
+             {}\n\n\
+             If it wasn't you, ignore this email and your password stays the same.\n",
+            user.name,
+            user.username,
+            expire_minutes,
+            url.replace("{token}", token)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_random_and_stored_hashed() {
+        let generated = generate();
+
+        assert_eq!(generated.token.len(), 64);
+        assert_eq!(generated.hash, hash(&generated.token));
+        assert_ne!(generated.token, generate().token);
+    }
+}
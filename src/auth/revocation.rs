@@ -0,0 +1,333 @@
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    prelude::{DateTimeWithTimeZone, Expr},
+    sea_query::OnConflict,
+    ActiveValue::NotSet,
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set,
+};
+
+use crate::{
+    auth::jwt::TokenClaims,
+    models::_entities::{personal_access_token, refresh_token, revoked_token, user},
+};
+
+/// How long a "not revoked" answer is trusted before the database is asked again.
+/// Revocations made by this process are visible immediately, the ones made by other
+/// instances within this window.
+const NOT_REVOKED_TTL: Duration = Duration::from_secs(30);
+
+/// How often entries past their `valid_until` are dropped.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most entries kept at once. Once full, further lookups go to the database until
+/// the next sweep makes room.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Revoked access tokens, keyed by `jti`.
+///
+/// The `revoked_token` table is the source of truth, lookups are cached in memory so
+/// the auth guard doesn't hit the database for every request.
+#[derive(Debug)]
+pub struct RevokedTokens {
+    cache: RwLock<Cache>,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct Cache {
+    entries: HashMap<String, CacheEntry>,
+    last_sweep: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CacheEntry {
+    revoked: bool,
+    valid_until: Instant,
+}
+
+impl Default for RevokedTokens {
+    fn default() -> Self {
+        Self::with_capacity(MAX_ENTRIES)
+    }
+}
+
+impl RevokedTokens {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            cache: RwLock::new(Cache {
+                entries: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+            capacity,
+        }
+    }
+
+    pub async fn is_revoked<C>(&self, db: &C, jti: &str) -> Result<bool, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(entry) = self.cached(jti) {
+            return Ok(entry.revoked);
+        }
+
+        let revoked = revoked_token::Entity::find()
+            .filter(revoked_token::Column::Jti.eq(jti))
+            .one(db)
+            .await?;
+
+        let entry = match revoked {
+            Some(revoked) => CacheEntry {
+                revoked: true,
+                valid_until: instant_at(revoked.expires_at.into()),
+            },
+            None => CacheEntry {
+                revoked: false,
+                valid_until: Instant::now() + NOT_REVOKED_TTL,
+            },
+        };
+
+        self.insert(jti, entry, Instant::now());
+
+        Ok(entry.revoked)
+    }
+
+    /// Revokes a token until it expires on its own.
+    pub async fn revoke<C>(
+        &self,
+        db: &C,
+        jti: &str,
+        user_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        revoked_token::Entity::insert(revoked_token::ActiveModel {
+            id: NotSet,
+            jti: Set(jti.to_string()),
+            user_id: Set(user_id),
+            expires_at: Set(expires_at.into()),
+            date_created: NotSet,
+        })
+        .on_conflict(
+            OnConflict::column(revoked_token::Column::Jti)
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await?;
+
+        // expired tokens are rejected by the signature check anyway
+        revoked_token::Entity::delete_many()
+            .filter(revoked_token::Column::ExpiresAt.lt(Utc::now()))
+            .exec(db)
+            .await?;
+
+        self.insert(
+            jti,
+            CacheEntry {
+                revoked: true,
+                valid_until: instant_at(expires_at),
+            },
+            Instant::now(),
+        );
+
+        Ok(())
+    }
+
+    fn cached(&self, jti: &str) -> Option<CacheEntry> {
+        let cache = self.cache.read().expect("revocation cache lock poisoned");
+
+        cache
+            .entries
+            .get(jti)
+            .filter(|entry| entry.valid_until > Instant::now())
+            .copied()
+    }
+
+    fn insert(&self, jti: &str, entry: CacheEntry, now: Instant) {
+        let mut cache = self.cache.write().expect("revocation cache lock poisoned");
+
+        let full = cache.entries.len() >= self.capacity && !cache.entries.contains_key(jti);
+
+        if full || now.duration_since(cache.last_sweep) >= SWEEP_INTERVAL {
+            cache.entries.retain(|_, entry| entry.valid_until > now);
+            cache.last_sweep = now;
+        }
+
+        // skipping the cache only costs a query, the table has the answer
+        if cache.entries.len() < self.capacity || cache.entries.contains_key(jti) {
+            cache.entries.insert(jti.to_string(), entry);
+        }
+    }
+}
+
+/// The user's `token_valid_after` after logging out everywhere with `before`, which
+/// can't lie in the future. The cutoff only ever moves forward, an earlier `before`
+/// mustn't bring back tokens a password change or an earlier log out had cut off.
+pub fn advance_cutoff(
+    token_valid_after: Option<DateTimeWithTimeZone>,
+    before: Option<DateTimeWithTimeZone>,
+    now: DateTimeWithTimeZone,
+) -> DateTimeWithTimeZone {
+    let before = before.map_or(now, |before| before.min(now));
+
+    token_valid_after.map_or(before, |cutoff| cutoff.max(before))
+}
+
+/// Ends every session of the user as of `now`, for when their credentials change:
+/// refresh tokens and personal access tokens are revoked and access tokens issued
+/// before are cut off. Meant to run in the transaction making the change.
+pub async fn revoke_sessions<C>(
+    db: &C,
+    user_id: i32,
+    now: DateTimeWithTimeZone,
+) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    user::Entity::update_many()
+        .col_expr(user::Column::TokenValidAfter, Expr::value(now))
+        .filter(user::Column::Id.eq(user_id))
+        .filter(
+            Condition::any()
+                .add(user::Column::TokenValidAfter.is_null())
+                .add(user::Column::TokenValidAfter.lt(now)),
+        )
+        .exec(db)
+        .await?;
+
+    refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    personal_access_token::Entity::update_many()
+        .col_expr(personal_access_token::Column::RevokedAt, Expr::value(now))
+        .filter(personal_access_token::Column::UserId.eq(user_id))
+        .filter(personal_access_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether the token was issued before the user's `token_valid_after`.
+pub fn is_cut_off(claims: &TokenClaims, token_valid_after: Option<DateTimeWithTimeZone>) -> bool {
+    token_valid_after.is_some_and(|cutoff| claims.issued_at() < cutoff)
+}
+
+fn instant_at(at: DateTime<Utc>) -> Instant {
+    let remaining = (at - Utc::now()).to_std().unwrap_or_default();
+
+    Instant::now() + remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::TokenType;
+
+    fn entry(valid_until: Instant) -> CacheEntry {
+        CacheEntry {
+            revoked: true,
+            valid_until,
+        }
+    }
+
+    fn keys(tokens: &RevokedTokens) -> Vec<String> {
+        let cache = tokens.cache.read().unwrap();
+        let mut keys: Vec<_> = cache.entries.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn expired_entries_are_swept_on_an_interval() {
+        let tokens = RevokedTokens::default();
+        let start = tokens.cache.read().unwrap().last_sweep;
+
+        tokens.insert("a", entry(start + Duration::from_secs(10)), start);
+        tokens.insert("b", entry(start + Duration::from_secs(600)), start);
+        tokens.insert("c", entry(start + Duration::from_secs(600)), start);
+
+        // "a" has expired but it isn't time to sweep yet
+        tokens.insert(
+            "c",
+            entry(start + Duration::from_secs(600)),
+            start + Duration::from_secs(20),
+        );
+        assert_eq!(keys(&tokens), vec!["a", "b", "c"]);
+
+        tokens.insert(
+            "d",
+            entry(start + Duration::from_secs(600)),
+            start + SWEEP_INTERVAL,
+        );
+        assert_eq!(keys(&tokens), vec!["b", "c", "d"]);
+    }
+
+    #[test]
+    fn stops_caching_when_full() {
+        let tokens = RevokedTokens::with_capacity(2);
+        let start = tokens.cache.read().unwrap().last_sweep;
+
+        tokens.insert("a", entry(start + Duration::from_secs(10)), start);
+        tokens.insert("b", entry(start + Duration::from_secs(600)), start);
+        tokens.insert("c", entry(start + Duration::from_secs(600)), start);
+        assert_eq!(keys(&tokens), vec!["a", "b"]);
+
+        // a full cache drops expired entries right away to make room
+        tokens.insert(
+            "c",
+            entry(start + Duration::from_secs(600)),
+            start + Duration::from_secs(20),
+        );
+        assert_eq!(keys(&tokens), vec!["b", "c"]);
+
+        // entries already cached are still updated
+        tokens.insert("b", entry(start), start + Duration::from_secs(20));
+        assert_eq!(tokens.cache.read().unwrap().entries["b"].valid_until, start);
+    }
+
+    #[test]
+    fn replaying_an_earlier_cutoff_keeps_old_tokens_out() {
+        let at = |value: &str| DateTimeWithTimeZone::parse_from_rfc3339(value).unwrap();
+
+        let mut token = TokenClaims::new("42", TokenType::Access, "family", 10);
+        token.iat_micros = at("2025-03-01T10:00:00.200Z").timestamp_micros();
+
+        // logged out everywhere in the same second the token was issued
+        let cutoff = advance_cutoff(None, None, at("2025-03-01T10:00:00.500Z"));
+        assert!(is_cut_off(&token, Some(cutoff)));
+
+        let replayed = advance_cutoff(
+            Some(cutoff),
+            Some(at("2025-03-01T09:00:00Z")),
+            at("2025-03-01T10:05:00Z"),
+        );
+        assert_eq!(replayed, cutoff);
+        assert!(is_cut_off(&token, Some(replayed)));
+
+        // a cutoff is never set in the future
+        let later = advance_cutoff(
+            Some(cutoff),
+            Some(at("2025-03-02T00:00:00Z")),
+            at("2025-03-01T10:05:00Z"),
+        );
+        assert_eq!(later, at("2025-03-01T10:05:00Z"));
+
+        token.iat_micros = at("2025-03-01T10:00:00.700Z").timestamp_micros();
+        assert!(!is_cut_off(&token, Some(cutoff)));
+        assert!(!is_cut_off(&token, None));
+    }
+}
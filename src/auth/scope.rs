@@ -0,0 +1,170 @@
+use std::{fmt, str::FromStr};
+
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+
+/// What a personal access token may do. Write scopes include reading the same
+/// resources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "tasks:read")]
+    TasksRead,
+    #[serde(rename = "tasks:write")]
+    TasksWrite,
+    #[serde(rename = "labels:read")]
+    LabelsRead,
+    #[serde(rename = "labels:write")]
+    LabelsWrite,
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+    /// Only of use to admins, like the routes it covers.
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 8] = [
+        Scope::TasksRead,
+        Scope::TasksWrite,
+        Scope::LabelsRead,
+        Scope::LabelsWrite,
+        Scope::ProfileRead,
+        Scope::ProfileWrite,
+        Scope::UsersRead,
+        Scope::UsersWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::TasksRead => "tasks:read",
+            Scope::TasksWrite => "tasks:write",
+            Scope::LabelsRead => "labels:read",
+            Scope::LabelsWrite => "labels:write",
+            Scope::ProfileRead => "profile:read",
+            Scope::ProfileWrite => "profile:write",
+            Scope::UsersRead => "users:read",
+            Scope::UsersWrite => "users:write",
+        }
+    }
+
+    pub fn grants(&self, required: Scope) -> bool {
+        *self == required
+            || matches!(
+                (self, required),
+                (Scope::TasksWrite, Scope::TasksRead)
+                    | (Scope::LabelsWrite, Scope::LabelsRead)
+                    | (Scope::ProfileWrite, Scope::ProfileRead)
+                    | (Scope::UsersWrite, Scope::UsersRead)
+            )
+    }
+
+    /// Scopes as stored in `personal_access_token.scopes`, separated by spaces.
+    pub fn join(scopes: &[Scope]) -> String {
+        scopes
+            .iter()
+            .map(Scope::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The reverse of [`Scope::join`], scopes that no longer exist are dropped.
+    pub fn split(scopes: &str) -> Vec<Scope> {
+        scopes
+            .split_whitespace()
+            .filter_map(|scope| scope.parse().ok())
+            .collect()
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+            .ok_or(())
+    }
+}
+
+/// The scope a personal access token needs for a route, given its matched path.
+/// `None` means the route is off limits to tokens altogether: signing out, changing
+/// the password and managing tokens take a login.
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    let read = matches!(*method, Method::GET | Method::HEAD);
+    let pick = |read_scope, write_scope| Some(if read { read_scope } else { write_scope });
+
+    let under = |prefix: &str| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    };
+
+    if under("/api/tasks") {
+        pick(Scope::TasksRead, Scope::TasksWrite)
+    } else if under("/api/labels") {
+        pick(Scope::LabelsRead, Scope::LabelsWrite)
+    } else if under("/api/users") {
+        pick(Scope::UsersRead, Scope::UsersWrite)
+    } else if path == "/api/me" || path == "/api/me/" || path == "/api/me/profile" {
+        pick(Scope::ProfileRead, Scope::ProfileWrite)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_map_to_scopes_by_method() {
+        assert_eq!(
+            required_scope(&Method::GET, "/api/tasks/{task_uuid}/comments"),
+            Some(Scope::TasksRead)
+        );
+        assert_eq!(
+            required_scope(&Method::PUT, "/api/tasks/{task_uuid}/update_status"),
+            Some(Scope::TasksWrite)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/labels/{label_id}"),
+            Some(Scope::LabelsWrite)
+        );
+        assert_eq!(
+            required_scope(&Method::PATCH, "/api/me/profile"),
+            Some(Scope::ProfileWrite)
+        );
+
+        assert_eq!(required_scope(&Method::POST, "/api/me/password"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/me/tokens"), None);
+        assert_eq!(required_scope(&Method::POST, "/api/auth/logout"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/tasksearch"), None);
+    }
+
+    #[test]
+    fn write_scopes_include_reading() {
+        assert!(Scope::TasksWrite.grants(Scope::TasksRead));
+        assert!(!Scope::TasksRead.grants(Scope::TasksWrite));
+        assert!(!Scope::TasksWrite.grants(Scope::LabelsRead));
+    }
+
+    #[test]
+    fn scopes_round_trip_through_the_column() {
+        let scopes = [Scope::TasksRead, Scope::LabelsWrite];
+        let joined = Scope::join(&scopes);
+
+        assert_eq!(joined, "tasks:read labels:write");
+        assert_eq!(Scope::split(&joined), scopes);
+        assert_eq!(Scope::split("tasks:read gone:away"), [Scope::TasksRead]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+
+use crate::{error::AppError, AppState};
+
+/// Longest user agent kept, the column is sized for it.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Who is making the request, as far as the server can tell.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl FromRequestParts<Arc<AppState>> for ClientInfo {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(
+            &parts.headers,
+            &parts.extensions,
+            state.config.rate_limit.trust_forwarded_for,
+        ))
+    }
+}
+
+impl ClientInfo {
+    pub fn from_parts(
+        headers: &HeaderMap,
+        extensions: &axum::http::Extensions,
+        trust_forwarded_for: bool,
+    ) -> Self {
+        let peer = extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(|user_agent| {
+                user_agent
+                    .chars()
+                    .take(MAX_USER_AGENT_LEN)
+                    .collect::<String>()
+            });
+
+        Self {
+            ip: client_ip(headers, peer, trust_forwarded_for),
+            user_agent,
+        }
+    }
+}
+
+/// The address of the client. Behind a proxy that's the last `X-Forwarded-For`
+/// entry, the one the proxy added; earlier entries come from the client and can be
+/// anything.
+fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+    trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+    let forwarded_for = trust_forwarded_for
+        .then(|| {
+            headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .next_back()
+                .and_then(|ip| ip.trim().parse().ok())
+        })
+        .flatten();
+
+    forwarded_for.or(peer.map(|peer| peer.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_for_is_only_trusted_when_configured() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", "1.1.1.1, 2.2.2.2".parse().unwrap());
+        headers.append("x-forwarded-for", "3.3.3.3".parse().unwrap());
+        let peer = Some("10.0.0.1:4000".parse().unwrap());
+
+        assert_eq!(
+            client_ip(&headers, peer, true),
+            Some("3.3.3.3".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(&headers, peer, false),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(client_ip(&HeaderMap::new(), None, true), None);
+    }
+}
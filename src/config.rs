@@ -0,0 +1,997 @@
+use std::{collections::HashMap, fmt, path::PathBuf, str::FromStr};
+
+use axum::http::HeaderValue;
+use clap::Parser;
+use lettre::message::Mailbox;
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+use crate::{models::_entities::sea_orm_active_enums::TaskStatus, rate_limit::Quota};
+
+/// Settings of the whole app, loaded once at startup. Later sources override earlier
+/// ones: defaults, the TOML file, environment variables, command line flags.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub pagination: PaginationConfig,
+    pub cors: CorsConfig,
+    pub logging: LoggingConfig,
+    pub rate_limit: RateLimitConfig,
+    pub mail: MailConfig,
+    pub storage: StorageConfig,
+    pub workflow: WorkflowConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "localhost:8000".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub connect_timeout_seconds: u64,
+    pub idle_timeout_seconds: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 10,
+            min_connections: 1,
+            connect_timeout_seconds: 8,
+            idle_timeout_seconds: 300,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Signs access and refresh tokens, and pagination cursors through a derived key.
+    pub jwt_secret: String,
+    pub access_token_expire_minutes: i64,
+    pub refresh_token_expire_minutes: i64,
+    /// How long the link of a password reset email works.
+    pub password_reset_token_minutes: i64,
+    pub lockout: LockoutConfig,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: String::new(),
+            access_token_expire_minutes: 10,
+            refresh_token_expire_minutes: 1440,
+            password_reset_token_minutes: 30,
+            lockout: LockoutConfig::default(),
+        }
+    }
+}
+
+// keeps the secret out of logs
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &"***")
+            .field(
+                "access_token_expire_minutes",
+                &self.access_token_expire_minutes,
+            )
+            .field(
+                "refresh_token_expire_minutes",
+                &self.refresh_token_expire_minutes,
+            )
+            .field(
+                "password_reset_token_minutes",
+                &self.password_reset_token_minutes,
+            )
+            .field("lockout", &self.lockout)
+            .finish()
+    }
+}
+
+/// Accounts are locked after `max_failed_logins` failed logins that are at most
+/// `failure_window_minutes` apart. The lockout lasts `lockout_minutes` and doubles with
+/// every further failure, up to `max_lockout_minutes`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockoutConfig {
+    pub max_failed_logins: i32,
+    pub failure_window_minutes: i64,
+    pub lockout_minutes: i64,
+    pub max_lockout_minutes: i64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failed_logins: 5,
+            failure_window_minutes: 15,
+            lockout_minutes: 15,
+            max_lockout_minutes: 1440,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationConfig {
+    pub per_page: u64,
+    pub max_per_page: u64,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            per_page: 10,
+            max_per_page: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser, `*` allows any. Empty disables
+    /// CORS.
+    pub allowed_origins: Vec<String>,
+    pub max_age_seconds: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            max_age_seconds: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `tracing` filter directives, e.g. `info` or `task_app=debug,sqlx=warn`.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "debug".to_string(),
+            format: LogFormat::Pretty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Pretty,
+    Compact,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pretty" => Ok(Self::Pretty),
+            "compact" => Ok(Self::Compact),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format '{}'", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Take the client address from the last `X-Forwarded-For` entry, only safe
+    /// behind a proxy that sets it.
+    pub trust_forwarded_for: bool,
+    /// Per client IP, on authenticated routes too before the token is checked.
+    pub anonymous: Quota,
+    /// Per user behind `auth_guard`.
+    pub authenticated: Quota,
+    /// Per client IP on login and registration, instead of `anonymous`.
+    pub login: Quota,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trust_forwarded_for: false,
+            anonymous: Quota::new(60, 60),
+            authenticated: Quota::new(300, 60),
+            login: Quota::new(5, 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    pub transport: MailTransport,
+    /// Sender of every email, e.g. `Task App <no-reply@example.com>`.
+    pub from: String,
+    /// Link sent in password reset emails, `{token}` is replaced with the reset token.
+    pub password_reset_url: String,
+    /// Where the `file` transport writes emails to.
+    pub file_dir: PathBuf,
+    pub smtp: SmtpConfig,
+    /// How often the outbox is checked for emails to send, besides right after
+    /// queueing one.
+    pub poll_interval_seconds: u64,
+    /// Deliveries of an email tried before it's given up on.
+    pub max_attempts: i32,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            transport: MailTransport::Smtp,
+            from: "Task App <no-reply@localhost>".to_string(),
+            password_reset_url: "http://localhost:3000/reset-password?token={token}".to_string(),
+            file_dir: PathBuf::from("mail"),
+            smtp: SmtpConfig::default(),
+            poll_interval_seconds: 5,
+            max_attempts: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    /// Logs the recipient and subject of emails instead of sending them, for development.
+    Log,
+    /// Writes every email to an `.eml` file in `file_dir`, for development.
+    File,
+    Smtp,
+}
+
+impl FromStr for MailTransport {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "log" => Ok(Self::Log),
+            "file" => Ok(Self::File),
+            "smtp" => Ok(Self::Smtp),
+            _ => Err(format!("unknown mail transport '{}'", value)),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    /// Both or neither of username and password.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub timeout_seconds: u64,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1025,
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            timeout_seconds: 10,
+        }
+    }
+}
+
+// keeps the password out of logs
+impl fmt::Debug for SmtpConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmtpConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("tls", &self.tls)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("timeout_seconds", &self.timeout_seconds)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, only for servers on the same host such as a local SMTP sink.
+    None,
+    /// Upgrades the connection with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// TLS from the start, usually on port 465.
+    Tls,
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Self::None),
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            _ => Err(format!("unknown SMTP TLS mode '{}'", value)),
+        }
+    }
+}
+
+/// Where attachments are kept and how much of them a user may upload.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// Directory the `local` backend keeps files in.
+    pub path: PathBuf,
+    pub s3: S3Config,
+    /// Largest single attachment.
+    pub max_attachment_size_mb: u64,
+    /// Total size of the attachments a user can keep.
+    pub attachment_quota_mb: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Local,
+            path: PathBuf::from("storage"),
+            s3: S3Config::default(),
+            max_attachment_size_mb: 25,
+            attachment_quota_mb: 250,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// `max_attachment_size_mb` in bytes.
+    pub fn max_attachment_size(&self) -> usize {
+        (self.max_attachment_size_mb * 1024 * 1024) as usize
+    }
+
+    /// `attachment_quota_mb` in bytes.
+    pub fn attachment_quota(&self) -> i64 {
+        (self.attachment_quota_mb * 1024 * 1024) as i64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Local,
+    /// An S3 compatible bucket, see `storage.s3`.
+    S3,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "local" => Ok(Self::Local),
+            "s3" => Ok(Self::S3),
+            _ => Err(format!("unknown storage backend '{}'", value)),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://localhost:9000` for MinIO.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+        }
+    }
+}
+
+// keeps the secret key out of logs
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"***")
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkflowConfig {
+    /// The statuses a task can move to from each status, keeping the status is
+    /// always allowed. Statuses left out can't be left.
+    pub transitions: HashMap<TaskStatus, Vec<TaskStatus>>,
+}
+
+impl Default for WorkflowConfig {
+    fn default() -> Self {
+        use TaskStatus::*;
+
+        Self {
+            transitions: HashMap::from([
+                (Pending, vec![InProgress, Done]),
+                (InProgress, vec![Pending, Done]),
+                (Done, vec![Reopened]),
+                (Reopened, vec![InProgress, Done]),
+            ]),
+        }
+    }
+}
+
+impl WorkflowConfig {
+    fn reaches(&self, from: TaskStatus, to: TaskStatus) -> bool {
+        let mut seen = vec![from];
+        let mut next = vec![from];
+
+        while let Some(status) = next.pop() {
+            if status == to {
+                return true;
+            }
+
+            for &target in self.transitions.get(&status).into_iter().flatten() {
+                if !seen.contains(&target) {
+                    seen.push(target);
+                    next.push(target);
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Command line flags, they win over every other source.
+#[derive(Debug, Default, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML config file, defaults to `config.toml` when it exists.
+    #[arg(long, short)]
+    pub config: Option<PathBuf>,
+    #[arg(long)]
+    pub address: Option<String>,
+    #[arg(long)]
+    pub database_url: Option<String>,
+    #[arg(long)]
+    pub log_level: Option<String>,
+}
+
+/// Everything wrong with the configuration, reported together.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl AppConfig {
+    pub fn load(cli: Cli) -> Result<Self, ConfigError> {
+        let mut problems = Vec::new();
+
+        let (path, required) = match (&cli.config, std::env::var("APP_CONFIG")) {
+            (Some(path), _) => (path.clone(), true),
+            (None, Ok(path)) => (PathBuf::from(path), true),
+            (None, Err(_)) => (PathBuf::from("config.toml"), false),
+        };
+
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|err| {
+                problems.push(format!("{}: {}", path.display(), err));
+                Self::default()
+            }),
+            Err(err) if required => {
+                problems.push(format!("{}: {}", path.display(), err));
+                Self::default()
+            }
+            Err(_) => Self::default(),
+        };
+
+        config.apply_env(|name| std::env::var(name).ok(), &mut problems);
+        config.apply_cli(cli);
+
+        problems.extend(config.validate());
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+
+    fn apply_env<F>(&mut self, var: F, problems: &mut Vec<String>)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        fn parse<T: FromStr>(name: &str, value: String, problems: &mut Vec<String>) -> Option<T> {
+            value
+                .parse()
+                .map_err(|_| problems.push(format!("{} has an invalid value '{}'", name, value)))
+                .ok()
+        }
+
+        macro_rules! env {
+            ($name:literal, $field:expr) => {
+                if let Some(value) = var($name) {
+                    if let Some(value) = parse($name, value, problems) {
+                        $field = value;
+                    }
+                }
+            };
+        }
+
+        env!("SERVER_ADDRESS", self.server.address);
+        env!("DATABASE_URL", self.database.url);
+        env!("DATABASE_MAX_CONNECTIONS", self.database.max_connections);
+        env!("DATABASE_MIN_CONNECTIONS", self.database.min_connections);
+        env!(
+            "DATABASE_CONNECT_TIMEOUT_SECONDS",
+            self.database.connect_timeout_seconds
+        );
+        env!(
+            "DATABASE_IDLE_TIMEOUT_SECONDS",
+            self.database.idle_timeout_seconds
+        );
+        env!("JWT_SECRET", self.auth.jwt_secret);
+        env!(
+            "ACCESS_TOKEN_EXPIRE_MINUTES",
+            self.auth.access_token_expire_minutes
+        );
+        env!(
+            "REFRESH_TOKEN_EXPIRE_MINUTES",
+            self.auth.refresh_token_expire_minutes
+        );
+        env!(
+            "LOCKOUT_MAX_FAILED_LOGINS",
+            self.auth.lockout.max_failed_logins
+        );
+        env!(
+            "LOCKOUT_FAILURE_WINDOW_MINUTES",
+            self.auth.lockout.failure_window_minutes
+        );
+        env!("LOCKOUT_MINUTES", self.auth.lockout.lockout_minutes);
+        env!("LOCKOUT_MAX_MINUTES", self.auth.lockout.max_lockout_minutes);
+        env!(
+            "PASSWORD_RESET_TOKEN_MINUTES",
+            self.auth.password_reset_token_minutes
+        );
+        env!("PER_PAGE", self.pagination.per_page);
+        env!("MAX_PER_PAGE", self.pagination.max_per_page);
+        env!("CORS_MAX_AGE_SECONDS", self.cors.max_age_seconds);
+        env!("LOG_LEVEL", self.logging.level);
+        // the usual name for filter directives wins when both are set
+        env!("RUST_LOG", self.logging.level);
+        env!("LOG_FORMAT", self.logging.format);
+
+        env!("RATE_LIMIT_ENABLED", self.rate_limit.enabled);
+        env!(
+            "RATE_LIMIT_TRUST_FORWARDED_FOR",
+            self.rate_limit.trust_forwarded_for
+        );
+        env!(
+            "RATE_LIMIT_ANONYMOUS_REQUESTS",
+            self.rate_limit.anonymous.requests
+        );
+        env!(
+            "RATE_LIMIT_ANONYMOUS_PERIOD_SECONDS",
+            self.rate_limit.anonymous.period_seconds
+        );
+        env!(
+            "RATE_LIMIT_AUTHENTICATED_REQUESTS",
+            self.rate_limit.authenticated.requests
+        );
+        env!(
+            "RATE_LIMIT_AUTHENTICATED_PERIOD_SECONDS",
+            self.rate_limit.authenticated.period_seconds
+        );
+        env!("RATE_LIMIT_LOGIN_REQUESTS", self.rate_limit.login.requests);
+        env!(
+            "RATE_LIMIT_LOGIN_PERIOD_SECONDS",
+            self.rate_limit.login.period_seconds
+        );
+
+        env!("MAIL_TRANSPORT", self.mail.transport);
+        env!("MAIL_FROM", self.mail.from);
+        env!("MAIL_PASSWORD_RESET_URL", self.mail.password_reset_url);
+        env!("MAIL_FILE_DIR", self.mail.file_dir);
+        env!(
+            "MAIL_POLL_INTERVAL_SECONDS",
+            self.mail.poll_interval_seconds
+        );
+        env!("MAIL_MAX_ATTEMPTS", self.mail.max_attempts);
+        env!("SMTP_HOST", self.mail.smtp.host);
+        env!("SMTP_PORT", self.mail.smtp.port);
+        env!("SMTP_TLS", self.mail.smtp.tls);
+        env!("SMTP_TIMEOUT_SECONDS", self.mail.smtp.timeout_seconds);
+
+        env!("STORAGE_BACKEND", self.storage.backend);
+        env!("STORAGE_PATH", self.storage.path);
+        env!(
+            "STORAGE_MAX_ATTACHMENT_SIZE_MB",
+            self.storage.max_attachment_size_mb
+        );
+        env!(
+            "STORAGE_ATTACHMENT_QUOTA_MB",
+            self.storage.attachment_quota_mb
+        );
+        env!("S3_ENDPOINT", self.storage.s3.endpoint);
+        env!("S3_BUCKET", self.storage.s3.bucket);
+        env!("S3_REGION", self.storage.s3.region);
+        env!("S3_ACCESS_KEY_ID", self.storage.s3.access_key_id);
+        env!("S3_SECRET_ACCESS_KEY", self.storage.s3.secret_access_key);
+
+        if let Some(username) = var("SMTP_USERNAME") {
+            self.mail.smtp.username = Some(username);
+        }
+
+        if let Some(password) = var("SMTP_PASSWORD") {
+            self.mail.smtp.password = Some(password);
+        }
+
+        if let Some(origins) = var("CORS_ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect();
+        }
+    }
+
+    fn apply_cli(&mut self, cli: Cli) {
+        if let Some(address) = cli.address {
+            self.server.address = address;
+        }
+
+        if let Some(database_url) = cli.database_url {
+            self.database.url = database_url;
+        }
+
+        if let Some(log_level) = cli.log_level {
+            self.logging.level = log_level;
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.server.address.is_empty() {
+            problems.push("server.address must be set".to_string());
+        }
+
+        if self.database.url.is_empty() {
+            problems.push("database.url (DATABASE_URL) must be set".to_string());
+        }
+
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+
+        if self.database.min_connections > self.database.max_connections {
+            problems.push(
+                "database.min_connections can't be more than database.max_connections".to_string(),
+            );
+        }
+
+        if self.auth.jwt_secret.is_empty() {
+            problems.push("auth.jwt_secret (JWT_SECRET) must be set".to_string());
+        }
+
+        if self.auth.access_token_expire_minutes <= 0 {
+            problems.push("auth.access_token_expire_minutes must be positive".to_string());
+        }
+
+        if self.auth.refresh_token_expire_minutes <= self.auth.access_token_expire_minutes {
+            problems.push(
+                "auth.refresh_token_expire_minutes must be longer than the access token's"
+                    .to_string(),
+            );
+        }
+
+        if self.auth.password_reset_token_minutes <= 0 {
+            problems.push("auth.password_reset_token_minutes must be positive".to_string());
+        }
+
+        let lockout = &self.auth.lockout;
+
+        if lockout.max_failed_logins < 1
+            || lockout.failure_window_minutes < 1
+            || lockout.lockout_minutes < 1
+        {
+            problems.push(
+                "auth.lockout settings must be at least 1, lockouts can't be disabled".to_string(),
+            );
+        }
+
+        if lockout.max_lockout_minutes < lockout.lockout_minutes {
+            problems.push(
+                "auth.lockout.max_lockout_minutes can't be less than auth.lockout.lockout_minutes"
+                    .to_string(),
+            );
+        }
+
+        if self.pagination.per_page == 0 {
+            problems.push("pagination.per_page must be at least 1".to_string());
+        }
+
+        if self.pagination.per_page > self.pagination.max_per_page {
+            problems
+                .push("pagination.per_page can't be more than pagination.max_per_page".to_string());
+        }
+
+        for origin in &self.cors.allowed_origins {
+            if origin != "*" && HeaderValue::from_str(origin).is_err() {
+                problems.push(format!(
+                    "cors.allowed_origins has an invalid origin '{}'",
+                    origin
+                ));
+            }
+        }
+
+        for (name, quota) in [
+            ("anonymous", self.rate_limit.anonymous),
+            ("authenticated", self.rate_limit.authenticated),
+            ("login", self.rate_limit.login),
+        ] {
+            if quota.requests == 0 || quota.period_seconds == 0 {
+                problems.push(format!(
+                    "rate_limit.{} needs at least 1 request per at least 1 second",
+                    name
+                ));
+            }
+        }
+
+        if self.mail.from.parse::<Mailbox>().is_err() {
+            problems.push(format!(
+                "mail.from is not a valid address '{}'",
+                self.mail.from
+            ));
+        }
+
+        if !self.mail.password_reset_url.contains("{token}") {
+            problems.push("mail.password_reset_url must contain {token}".to_string());
+        }
+
+        if self.mail.poll_interval_seconds == 0 || self.mail.max_attempts < 1 {
+            problems.push(
+                "mail.poll_interval_seconds and mail.max_attempts must be at least 1".to_string(),
+            );
+        }
+
+        if self.mail.transport == MailTransport::Smtp && self.mail.smtp.host.is_empty() {
+            problems.push("mail.smtp.host (SMTP_HOST) must be set".to_string());
+        }
+
+        if self.mail.smtp.username.is_some() != self.mail.smtp.password.is_some() {
+            problems.push("mail.smtp.username and mail.smtp.password go together".to_string());
+        }
+
+        let storage = &self.storage;
+
+        if storage.max_attachment_size_mb == 0 {
+            problems.push("storage.max_attachment_size_mb must be at least 1".to_string());
+        }
+
+        if storage.attachment_quota_mb < storage.max_attachment_size_mb {
+            problems.push(
+                "storage.attachment_quota_mb can't be less than storage.max_attachment_size_mb"
+                    .to_string(),
+            );
+        }
+
+        if storage.backend == StorageBackend::Local && storage.path.as_os_str().is_empty() {
+            problems.push("storage.path (STORAGE_PATH) must be set".to_string());
+        }
+
+        if storage.backend == StorageBackend::S3 {
+            let s3 = &storage.s3;
+
+            for (name, env, value) in [
+                ("endpoint", "S3_ENDPOINT", &s3.endpoint),
+                ("bucket", "S3_BUCKET", &s3.bucket),
+                ("region", "S3_REGION", &s3.region),
+                ("access_key_id", "S3_ACCESS_KEY_ID", &s3.access_key_id),
+                (
+                    "secret_access_key",
+                    "S3_SECRET_ACCESS_KEY",
+                    &s3.secret_access_key,
+                ),
+            ] {
+                if value.is_empty() {
+                    problems.push(format!("storage.s3.{} ({}) must be set", name, env));
+                }
+            }
+
+            let http_url = reqwest::Url::parse(&s3.endpoint)
+                .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+
+            if !s3.endpoint.is_empty() && !http_url {
+                problems.push(format!(
+                    "storage.s3.endpoint is not an http(s) URL '{}'",
+                    s3.endpoint
+                ));
+            }
+        }
+
+        // every task starts out pending, a workflow that can't get it done is a mistake
+        if !self.workflow.reaches(TaskStatus::Pending, TaskStatus::Done) {
+            problems.push("workflow.transitions must lead from pending to done".to_string());
+        }
+
+        if let Err(err) = EnvFilter::try_new(&self.logging.level) {
+            problems.push(format!("logging.level is invalid: {}", err));
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn later_sources_win() {
+        let mut config: AppConfig = toml::from_str(
+            r#"
+            [database]
+            url = "sqlite://from-file.db"
+            max_connections = 4
+
+            [pagination]
+            per_page = 20
+            "#,
+        )
+        .unwrap();
+
+        let env = HashMap::from([("PER_PAGE", "25"), ("JWT_SECRET", "secret")]);
+        let mut problems = Vec::new();
+        config.apply_env(|name| env.get(name).map(|v| v.to_string()), &mut problems);
+        config.apply_cli(Cli {
+            database_url: Some("sqlite://from-cli.db".to_string()),
+            ..Default::default()
+        });
+
+        assert!(problems.is_empty());
+        assert_eq!(config.database.url, "sqlite://from-cli.db");
+        assert_eq!(config.database.max_connections, 4);
+        assert_eq!(config.pagination.per_page, 25);
+        assert_eq!(config.pagination.max_per_page, 100);
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn example_file_matches_the_settings() {
+        let config: AppConfig = toml::from_str(include_str!("../config.example.toml")).unwrap();
+
+        assert_eq!(config.logging.format, LogFormat::Pretty);
+        assert_eq!(
+            config.workflow.transitions,
+            WorkflowConfig::default().transitions
+        );
+    }
+
+    #[test]
+    fn s3_storage_needs_its_settings() {
+        let mut config = AppConfig::default();
+        let env = HashMap::from([
+            ("DATABASE_URL", "sqlite://test.db"),
+            ("JWT_SECRET", "secret"),
+            ("STORAGE_BACKEND", "s3"),
+            ("S3_ENDPOINT", "localhost:9000"),
+            ("S3_BUCKET", "tasks"),
+        ]);
+        let mut problems = Vec::new();
+        config.apply_env(|name| env.get(name).map(|v| v.to_string()), &mut problems);
+        problems.extend(config.validate());
+
+        assert_eq!(
+            problems,
+            vec![
+                "storage.s3.access_key_id (S3_ACCESS_KEY_ID) must be set",
+                "storage.s3.secret_access_key (S3_SECRET_ACCESS_KEY) must be set",
+                "storage.s3.endpoint is not an http(s) URL 'localhost:9000'",
+            ]
+        );
+    }
+
+    #[test]
+    fn workflow_must_get_tasks_done() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [workflow.transitions]
+            pending = ["in_progress"]
+            in_progress = ["pending"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(config
+            .validate()
+            .contains(&"workflow.transitions must lead from pending to done".to_string()));
+
+        assert!(toml::from_str::<AppConfig>(
+            r#"
+            [workflow.transitions]
+            pending = ["finished"]
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let mut config = AppConfig::default();
+        let env = HashMap::from([("PER_PAGE", "many"), ("MAX_PER_PAGE", "0")]);
+        let mut problems = Vec::new();
+        config.apply_env(|name| env.get(name).map(|v| v.to_string()), &mut problems);
+        problems.extend(config.validate());
+
+        assert_eq!(
+            problems,
+            vec![
+                "PER_PAGE has an invalid value 'many'",
+                "database.url (DATABASE_URL) must be set",
+                "auth.jwt_secret (JWT_SECRET) must be set",
+                "pagination.per_page can't be more than pagination.max_per_page",
+            ]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{multipart::MultipartError, DefaultBodyLimit, Multipart, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, DbErr, EntityTrait, ModelTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait as _,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api_response::JsonResponse,
+    config::StorageConfig,
+    error::AppError,
+    models::{
+        _entities::{attachment, task, user},
+        attachment::total_size_for_user,
+    },
+    serializer::AttachmentSerializer,
+    storage::{ByteRange, RangeNotSatisfiable},
+    AppState,
+};
+
+pub async fn get_routes(storage: &StorageConfig) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_attachments).post(upload_attachment))
+        .route(
+            "/{attachment_uuid}",
+            get(download_attachment).delete(delete_attachment),
+        )
+        // room for the multipart framing around the file
+        .layer(DefaultBodyLimit::max(
+            storage.max_attachment_size() + 64 * 1024,
+        ))
+}
+
+pub async fn get_attachments(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+
+    let attachments: Vec<AttachmentSerializer> = task
+        .find_related(attachment::Entity)
+        .order_by(attachment::Column::DateCreated, sea_orm::Order::Asc)
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(AttachmentSerializer::from)
+        .collect();
+
+    Ok(JsonResponse::data(attachments, None))
+}
+
+/// Takes a `multipart/form-data` body with the file in a `file` field.
+pub async fn upload_attachment(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+
+    let limits = &app_state.config.storage;
+    let quota = limits.attachment_quota();
+    let quota_left = quota - total_size_for_user(&app_state.db, user_model.id).await?;
+
+    let mut upload = None;
+
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| multipart_error(err, limits))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let filename = sanitize_filename(field.file_name().unwrap_or("attachment"));
+        let content_type = field
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+
+        let mut data = Vec::new();
+        let mut hasher = Sha256::new();
+
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|err| multipart_error(err, limits))?
+        {
+            data.extend_from_slice(&chunk);
+            hasher.update(&chunk);
+
+            if data.len() > limits.max_attachment_size() {
+                return Err(too_large(limits));
+            }
+
+            if data.len() as i64 > quota_left {
+                return Err(quota_exceeded(limits));
+            }
+        }
+
+        upload = Some((filename, content_type, data, hex::encode(hasher.finalize())));
+        break;
+    }
+
+    let (filename, content_type, data, checksum) =
+        upload.ok_or(AppError::GenericError("Missing 'file' field.".to_string()))?;
+
+    let uuid = uuid::Uuid::new_v4().to_string();
+    let storage_key = format!("attachments/{}/{}", user_model.id, uuid);
+    let size = data.len() as i64;
+
+    app_state
+        .storage
+        .put(&storage_key, Bytes::from(data))
+        .await?;
+
+    let user_id = user_model.id;
+    let attachment_model = attachment::ActiveModel {
+        id: NotSet,
+        uuid: Set(uuid),
+        task_id: Set(task.id),
+        user_id: Set(user_id),
+        filename: Set(filename),
+        content_type: Set(content_type),
+        size: Set(size),
+        checksum: Set(checksum),
+        storage_key: Set(storage_key.clone()),
+        date_created: NotSet,
+    };
+
+    // the check above only rejects large uploads early, concurrent uploads are
+    // serialised on the user row so they can't overshoot the quota together
+    let attachment = app_state
+        .db
+        .transaction::<_, Option<attachment::Model>, DbErr>(|txn| {
+            Box::pin(async move {
+                user::Entity::find_by_id(user_id)
+                    .lock_exclusive()
+                    .one(txn)
+                    .await?;
+
+                if total_size_for_user(txn, user_id).await? + size > quota {
+                    return Ok(None);
+                }
+
+                attachment_model.insert(txn).await.map(Some)
+            })
+        })
+        .await
+        .map_err(AppError::from)
+        .and_then(|attachment| attachment.ok_or_else(|| quota_exceeded(limits)));
+
+    if attachment.is_err() {
+        if let Err(err) = app_state.storage.delete(&storage_key).await {
+            tracing::warn!("Could not remove orphaned upload {}: {}", storage_key, err);
+        }
+    }
+
+    let attachment = attachment?;
+
+    Ok((
+        StatusCode::CREATED,
+        JsonResponse::data(
+            AttachmentSerializer::from(attachment),
+            Some("Attachment uploaded successfully".to_string()),
+        ),
+    ))
+}
+
+/// Streams the file, honouring a single `Range` if one is requested.
+pub async fn download_attachment(
+    State(app_state): State<Arc<AppState>>,
+    Path((task_uuid, attachment_uuid)): Path<(String, String)>,
+    Extension(user_model): Extension<user::Model>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+    let attachment = find_attachment(&app_state, &task, attachment_uuid).await?;
+
+    let size = attachment.size as u64;
+
+    let range = match headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .map(|range| ByteRange::parse(range, size))
+        .transpose()
+    {
+        Ok(range) => range.flatten(),
+        Err(RangeNotSatisfiable) => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", size))],
+            )
+                .into_response());
+        }
+    };
+
+    let stream = app_state
+        .storage
+        .get(&attachment.storage_key, range)
+        .await?;
+
+    let mut response = Response::builder()
+        .header(
+            header::CONTENT_TYPE,
+            served_content_type(&attachment.content_type),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, format!("\"{}\"", attachment.checksum))
+        .header(
+            header::CONTENT_DISPOSITION,
+            content_disposition(&attachment.filename),
+        );
+
+    response = match range {
+        Some(range) => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_LENGTH, range.len())
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end, size),
+            ),
+        None => response
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, size),
+    };
+
+    response
+        .body(Body::from_stream(stream))
+        .map_err(|e| AppError::GenericError(e.to_string()))
+}
+
+pub async fn delete_attachment(
+    State(app_state): State<Arc<AppState>>,
+    Path((task_uuid, attachment_uuid)): Path<(String, String)>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+    let attachment = find_attachment(&app_state, &task, attachment_uuid).await?;
+
+    let storage_key = attachment.storage_key.clone();
+
+    attachment.delete(&app_state.db).await?;
+
+    app_state.storage.delete(&storage_key).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Attachment deleted successfully".to_string()),
+    ))
+}
+
+async fn find_task(
+    app_state: &AppState,
+    user_model: &user::Model,
+    task_uuid: String,
+) -> Result<task::Model, AppError> {
+    Ok(user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Task not found.".into()))?)
+}
+
+async fn find_attachment(
+    app_state: &AppState,
+    task: &task::Model,
+    attachment_uuid: String,
+) -> Result<attachment::Model, AppError> {
+    Ok(task
+        .find_related(attachment::Entity)
+        .filter(attachment::Column::Uuid.eq(attachment_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Attachment not found.".into()))?)
+}
+
+fn too_large(limits: &StorageConfig) -> AppError {
+    AppError::PayloadTooLarge(format!(
+        "Attachments can't be larger than {} MB.",
+        limits.max_attachment_size_mb
+    ))
+}
+
+/// The body limit trips before the size check for bodies well over the limit.
+fn multipart_error(err: MultipartError, limits: &StorageConfig) -> AppError {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return too_large(limits);
+    }
+
+    AppError::GenericError(err.body_text())
+}
+
+fn quota_exceeded(limits: &StorageConfig) -> AppError {
+    AppError::PayloadTooLarge(format!(
+        "Attachment quota of {} MB exceeded.",
+        limits.attachment_quota_mb
+    ))
+}
+
+/// Keeps only the last path component and drops control characters, browsers send
+/// full paths in some cases.
+fn sanitize_filename(filename: &str) -> String {
+    let filename: String = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(255)
+        .collect();
+
+    if filename.is_empty() || filename == "." || filename == ".." {
+        "attachment".to_string()
+    } else {
+        filename
+    }
+}
+
+/// Uploaded types that are sent back as they are.
+const SAFE_CONTENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "application/zip",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "text/csv",
+    "text/plain",
+];
+
+/// The content type an attachment is downloaded with. The uploaded one comes from the
+/// client, and HTML or SVG served from the API's origin could run script there, so
+/// only [`SAFE_CONTENT_TYPES`] are kept.
+fn served_content_type(content_type: &str) -> &str {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+    if SAFE_CONTENT_TYPES
+        .iter()
+        .any(|safe| essence.eq_ignore_ascii_case(safe))
+    {
+        content_type
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// `attachment` disposition with an ASCII fallback name and the real one in
+/// `filename*` (RFC 6266).
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+
+    let encoded: String = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_safe_types_are_served_as_uploaded() {
+        assert_eq!(served_content_type("image/png"), "image/png");
+        assert_eq!(
+            served_content_type("text/plain; charset=utf-8"),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(served_content_type("text/html"), "application/octet-stream");
+        assert_eq!(
+            served_content_type("image/svg+xml"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            served_content_type("TEXT/HTML; charset=utf-8"),
+            "application/octet-stream"
+        );
+    }
+}
@@ -2,68 +2,421 @@ use std::sync::Arc;
 
 use crate::{
     api_response::JsonResponse,
-    auth::jwt::{create_user_token, UserToken},
+    auth::{
+        jwt::{create_user_token, decode_user_token, TokenClaims, TokenType, UserToken},
+        lockout::{self, LoginFailures},
+        password::{hash_blocking, verify_blocking, PasswordHasher},
+        password_reset, revocation,
+    },
+    client_info::ClientInfo,
+    config::AuthConfig,
     error::AppError,
-    form::user_form::{CreateUserRequest, UserLogin},
-    models::_entities::{user, user_profile},
+    form::user_form::{
+        CreateUserRequest, ForgotPasswordRequest, LogoutAllRequest, RefreshTokenRequest,
+        ResetPasswordRequest, UserLogin,
+    },
+    mailer::outbox,
+    models::_entities::{
+        login_attempt, password_reset_token, refresh_token,
+        sea_orm_active_enums::LoginFailureReason, user, user_profile,
+    },
     serializer::UserWithProfileSerializer,
-    utils::verify_password,
     AppState,
 };
 
-use axum::{extract::State, response::IntoResponse, routing::post, Json, Router};
+use axum::{extract::State, response::IntoResponse, routing::post, Extension, Json, Router};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, QueryFilter, Set,
-    TransactionTrait,
+    prelude::{DateTimeWithTimeZone, Expr},
+    ActiveModelTrait,
+    ActiveValue::NotSet,
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter, Set, TransactionTrait,
 };
 use validator::Validate;
 
-pub async fn get_routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/auth/login", post(login))
-        .route("/auth/logout", post(logout))
-        .route("/auth/register", post(register))
-}
-
 pub async fn get_login_route() -> Router<Arc<AppState>> {
     Router::new().route("/login", post(login))
 }
 
 pub async fn get_logout_route() -> Router<Arc<AppState>> {
-    Router::new().route("/logout", post(logout))
+    Router::new()
+        .route("/logout", post(logout))
+        .route("/logout_all", post(logout_all))
 }
 
 pub async fn get_register_route() -> Router<Arc<AppState>> {
     Router::new().route("/register", post(register))
 }
 
+pub async fn get_refresh_route() -> Router<Arc<AppState>> {
+    Router::new().route("/refresh", post(refresh))
+}
+
+pub async fn get_password_reset_route() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/password/forgot", post(forgot_password))
+        .route("/password/reset", post(reset_password))
+}
+
+/// A new reset link is only sent this long after the last one, so the endpoint can't
+/// be used to flood someone's inbox.
+const PASSWORD_RESET_COOLDOWN: chrono::Duration = chrono::Duration::minutes(1);
+
+/// The same answer for every failed login, so it doesn't tell whether a username
+/// exists or an account is locked.
+fn invalid_credentials() -> AppError {
+    AppError::Unauthorized("Invalid credentials.".to_string())
+}
+
 #[axum::debug_handler]
 pub async fn login(
     State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(user_login): Json<UserLogin>,
 ) -> Result<impl IntoResponse, AppError> {
+    let password_hasher = &app_state.password_hasher;
+    let now = chrono::Utc::now();
+
     let user = user::Entity::find()
-        .filter(user::Column::Username.eq(user_login.username))
+        .filter(user::Column::Username.eq(&user_login.username))
         .one(&app_state.db)
-        .await?
-        .ok_or(AppError::GenericError("User not found.".to_string()))?;
+        .await?;
+
+    let Some(user) = user else {
+        // hash anyway, so response times don't tell unknown usernames apart
+        verify_blocking(
+            password_hasher,
+            dummy_password_hash(password_hasher).await?,
+            &user_login.password,
+        )
+        .await?;
+
+        record_login_attempt(
+            &app_state.db,
+            &user_login.username,
+            None,
+            &client,
+            Some(LoginFailureReason::UnknownUser),
+        )
+        .await?;
+
+        return Err(invalid_credentials());
+    };
 
-    if !verify_password(&user.password, &user_login.password)? {
-        return Err(AppError::GenericError("Invalid user".to_string()));
+    let failures = LoginFailures::from(&user);
+
+    if failures.is_locked(now) {
+        // hash anyway, so response times don't tell locked accounts apart
+        verify_blocking(
+            password_hasher,
+            dummy_password_hash(password_hasher).await?,
+            &user_login.password,
+        )
+        .await?;
+
+        record_login_attempt(
+            &app_state.db,
+            &user_login.username,
+            Some(user.id),
+            &client,
+            Some(LoginFailureReason::Locked),
+        )
+        .await?;
+
+        return Err(invalid_credentials());
+    }
+
+    if !verify_blocking(password_hasher, &user.password, &user_login.password).await? {
+        let failures =
+            lockout::record_failure(&app_state.db, user.id, now, &app_state.config.auth.lockout)
+                .await?;
+
+        if let Some(locked_until) = failures.locked_until.filter(|until| *until > now) {
+            tracing::warn!(
+                user_id = user.id,
+                failed_logins = failures.count,
+                "Account locked until {}",
+                locked_until
+            );
+        }
+
+        record_login_attempt(
+            &app_state.db,
+            &user_login.username,
+            Some(user.id),
+            &client,
+            Some(LoginFailureReason::InvalidPassword),
+        )
+        .await?;
+
+        return Err(invalid_credentials());
     }
 
-    let access_token = create_user_token(&user.email, 10).await;
-    let refresh_token = create_user_token(&user.email, 1440).await;
+    let needs_rehash = password_hasher.needs_rehash(&user.password);
+    let has_failures = user.failed_login_count > 0 || user.locked_until.is_some();
 
-    let user_token = UserToken {
-        access_token,
-        refresh_token: Some(refresh_token),
+    let user = if needs_rehash || has_failures {
+        let mut user: user::ActiveModel = user.into();
+
+        // upgrade hashes made with an older scheme or weaker parameters
+        if needs_rehash {
+            user.password = Set(hash_blocking(password_hasher, &user_login.password).await?);
+        }
+
+        user.failed_login_count = Set(0);
+        user.last_failed_login_at = Set(None);
+        user.locked_until = Set(None);
+
+        user.update(&app_state.db).await?
+    } else {
+        user
     };
 
+    record_login_attempt(
+        &app_state.db,
+        &user_login.username,
+        Some(user.id),
+        &client,
+        None,
+    )
+    .await?;
+
+    // every login starts a new refresh token family
+    let family = uuid::Uuid::new_v4().to_string();
+
+    let user_token = issue_user_token(&app_state.db, &app_state.config.auth, &user, family).await?;
+
     Ok(JsonResponse::data(user_token, None))
 }
 
-pub async fn logout() {}
+#[axum::debug_handler]
+pub async fn refresh(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let token_claims = decode_user_token(
+        &payload.refresh_token,
+        TokenType::Refresh,
+        &app_state.config.auth.jwt_secret,
+    )?;
+
+    let stored_token = refresh_token::Entity::find()
+        .filter(refresh_token::Column::Jti.eq(token_claims.jti))
+        .one(&app_state.db)
+        .await?
+        .ok_or(AppError::Unauthorized(
+            "Authentication credentials are invalid.".to_string(),
+        ))?;
+
+    let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+
+    let txn = app_state.db.begin().await?;
+
+    // a refresh token can be exchanged only once, the conditional update makes sure
+    // concurrent requests with the same token can't both succeed
+    let claimed = refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::UsedAt, Expr::value(now))
+        .filter(refresh_token::Column::Id.eq(stored_token.id))
+        .filter(refresh_token::Column::UsedAt.is_null())
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(&txn)
+        .await?;
+
+    if claimed.rows_affected == 0 {
+        txn.rollback().await?;
+
+        // a rotated token coming back means it has leaked, so the whole family goes
+        if stored_token.revoked_at.is_none() {
+            tracing::warn!(
+                "Refresh token reuse detected for user {}, revoking family {}",
+                stored_token.user_id,
+                stored_token.family
+            );
+
+            revoke_refresh_token_family(&app_state.db, &stored_token.family).await?;
+        }
+
+        return Err(AppError::Unauthorized(
+            "Authentication credentials are invalid.".to_string(),
+        ));
+    }
+
+    let user = user::Entity::find_by_id(stored_token.user_id)
+        .one(&txn)
+        .await?
+        .ok_or(AppError::Unauthorized(
+            "Authentication credentials are invalid.".to_string(),
+        ))?;
+
+    let user_token =
+        issue_user_token(&txn, &app_state.config.auth, &user, stored_token.family).await?;
+
+    txn.commit().await?;
+
+    Ok(JsonResponse::data(user_token, None))
+}
+
+/// A hash no password matches, verified against when the username is unknown.
+async fn dummy_password_hash(
+    password_hasher: &Arc<dyn PasswordHasher>,
+) -> Result<&'static str, AppError> {
+    static DUMMY_PASSWORD_HASH: tokio::sync::OnceCell<String> = tokio::sync::OnceCell::const_new();
+
+    DUMMY_PASSWORD_HASH
+        .get_or_try_init(|| async {
+            hash_blocking(password_hasher, &uuid::Uuid::new_v4().to_string()).await
+        })
+        .await
+        .map(String::as_str)
+}
+
+async fn record_login_attempt<C>(
+    db: &C,
+    username: &str,
+    user_id: Option<i32>,
+    client: &ClientInfo,
+    failure_reason: Option<LoginFailureReason>,
+) -> Result<(), AppError>
+where
+    C: ConnectionTrait,
+{
+    login_attempt::ActiveModel {
+        id: NotSet,
+        username: Set(username.chars().take(255).collect()),
+        user_id: Set(user_id),
+        ip_address: Set(client.ip.map(|ip| ip.to_string())),
+        user_agent: Set(client.user_agent.clone()),
+        succeeded: Set(failure_reason.is_none()),
+        failure_reason: Set(failure_reason),
+        date_created: NotSet,
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
+
+/// A new access and refresh token pair in `family`, the refresh token is stored so it
+/// can be rotated and revoked.
+pub async fn issue_user_token<C>(
+    db: &C,
+    auth_config: &AuthConfig,
+    user: &user::Model,
+    family: String,
+) -> Result<UserToken, AppError>
+where
+    C: ConnectionTrait,
+{
+    let subject = user.id.to_string();
+
+    let access_claims = TokenClaims::new(
+        &subject,
+        TokenType::Access,
+        &family,
+        auth_config.access_token_expire_minutes,
+    );
+    let refresh_claims = TokenClaims::new(
+        &subject,
+        TokenType::Refresh,
+        &family,
+        auth_config.refresh_token_expire_minutes,
+    );
+
+    refresh_token::ActiveModel {
+        id: NotSet,
+        jti: Set(refresh_claims.jti.clone()),
+        family: Set(family),
+        user_id: Set(user.id),
+        expires_at: Set(refresh_claims.expires_at().into()),
+        used_at: NotSet,
+        revoked_at: NotSet,
+        date_created: NotSet,
+    }
+    .insert(db)
+    .await?;
+
+    Ok(UserToken {
+        access_token: create_user_token(&access_claims, &auth_config.jwt_secret).await,
+        refresh_token: Some(create_user_token(&refresh_claims, &auth_config.jwt_secret).await),
+    })
+}
+
+async fn revoke_refresh_token_family<C>(db: &C, family: &str) -> Result<(), AppError>
+where
+    C: ConnectionTrait,
+{
+    let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+
+    refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+        .filter(refresh_token::Column::Family.eq(family))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+#[axum::debug_handler]
+pub async fn logout(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+    Extension(token_claims): Extension<TokenClaims>,
+) -> Result<impl IntoResponse, AppError> {
+    app_state
+        .revoked_tokens
+        .revoke(
+            &app_state.db,
+            &token_claims.jti,
+            user.id,
+            token_claims.expires_at(),
+        )
+        .await?;
+
+    revoke_refresh_token_family(&app_state.db, &token_claims.family).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Logged out successfully".to_string()),
+    ))
+}
+
+/// Invalidates every token issued to the user before `before` (defaults to now).
+#[axum::debug_handler]
+pub async fn logout_all(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+    payload: Option<Json<LogoutAllRequest>>,
+) -> Result<impl IntoResponse, AppError> {
+    let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+
+    let before = payload.and_then(|Json(payload)| payload.before);
+    let cutoff = revocation::advance_cutoff(user.token_valid_after, before, now);
+
+    refresh_token::Entity::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+        .filter(refresh_token::Column::UserId.eq(user.id))
+        .filter(refresh_token::Column::DateCreated.lt(cutoff))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(&app_state.db)
+        .await?;
+
+    // also when a concurrent password change has moved it further meanwhile
+    user::Entity::update_many()
+        .col_expr(user::Column::TokenValidAfter, Expr::value(cutoff))
+        .filter(user::Column::Id.eq(user.id))
+        .filter(
+            Condition::any()
+                .add(user::Column::TokenValidAfter.is_null())
+                .add(user::Column::TokenValidAfter.lt(cutoff)),
+        )
+        .exec(&app_state.db)
+        .await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Logged out from all sessions successfully".to_string()),
+    ))
+}
 
 #[axum::debug_handler]
 pub async fn register(
@@ -72,13 +425,16 @@ pub async fn register(
 ) -> Result<impl IntoResponse, AppError> {
     user_request.validate()?;
 
+    let user = user_request
+        .clone()
+        .into_active_model(&app_state.password_hasher)
+        .await?;
+
     let user_with_profile = app_state
         .db
         .transaction::<_, (user::Model, Option<user_profile::Model>), sea_orm::DbErr>(|txn| {
             Box::pin(async move {
-                let user = user::ActiveModel::from(user_request.clone())
-                    .insert(txn)
-                    .await?;
+                let user = user.insert(txn).await?;
 
                 let user_profile = user_profile::ActiveModel {
                     id: NotSet,
@@ -92,10 +448,158 @@ pub async fn register(
                 Ok((user, Some(user_profile)))
             })
         })
-        .await
-        .map_err(|e| AppError::GenericError(e.to_string()))?; // should be database error
+        .await?;
 
     let user_serializer = UserWithProfileSerializer::from(user_with_profile);
 
     Ok(JsonResponse::data(user_serializer, None))
 }
+
+/// Emails a password reset link when the address belongs to an account. The answer is
+/// the same either way, so it doesn't tell which addresses are registered.
+#[axum::debug_handler]
+pub async fn forgot_password(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let user = user::Entity::find()
+        .filter(user::Column::Email.eq(&payload.email))
+        .one(&app_state.db)
+        .await?;
+
+    if let Some(user) = user {
+        send_password_reset(&app_state, &user).await?;
+    }
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("If the address belongs to an account, a reset link has been sent to it".to_string()),
+    ))
+}
+
+async fn send_password_reset(app_state: &AppState, user: &user::Model) -> Result<(), AppError> {
+    let now = chrono::Utc::now();
+    let expire_minutes = app_state.config.auth.password_reset_token_minutes;
+
+    let recent = password_reset_token::Entity::find()
+        .filter(password_reset_token::Column::UserId.eq(user.id))
+        .filter(password_reset_token::Column::DateCreated.gt(now - PASSWORD_RESET_COOLDOWN))
+        .one(&app_state.db)
+        .await?;
+
+    if recent.is_some() {
+        return Ok(());
+    }
+
+    let generated = password_reset::generate();
+    let email = password_reset::reset_email(
+        user,
+        &app_state.config.mail.password_reset_url,
+        &generated.token,
+        expire_minutes,
+    );
+
+    let txn = app_state.db.begin().await?;
+
+    // only the latest link works
+    password_reset_token::Entity::update_many()
+        .col_expr(
+            password_reset_token::Column::ExpiresAt,
+            Expr::value(DateTimeWithTimeZone::from(now)),
+        )
+        .filter(password_reset_token::Column::UserId.eq(user.id))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .filter(password_reset_token::Column::ExpiresAt.gt(now))
+        .exec(&txn)
+        .await?;
+
+    password_reset_token::ActiveModel {
+        id: NotSet,
+        user_id: Set(user.id),
+        token_hash: Set(generated.hash),
+        expires_at: Set((now + chrono::Duration::minutes(expire_minutes)).into()),
+        used_at: NotSet,
+        date_created: Set(now.into()),
+    }
+    .insert(&txn)
+    .await?;
+
+    outbox::enqueue(&txn, email).await?;
+
+    txn.commit().await?;
+
+    app_state.outbox.wake();
+
+    tracing::info!(user_id = user.id, "Password reset requested");
+
+    Ok(())
+}
+
+/// Sets a new password with the token from a reset email. Every session of the user
+/// ends, and a lockout is lifted.
+#[axum::debug_handler]
+pub async fn reset_password(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let now = chrono::Utc::now();
+    let now_tz: DateTimeWithTimeZone = now.into();
+
+    let invalid_token =
+        || AppError::GenericError("The reset link is invalid or has expired.".to_string());
+
+    let stored_token = password_reset_token::Entity::find()
+        .filter(password_reset_token::Column::TokenHash.eq(password_reset::hash(&payload.token)))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .filter(password_reset_token::Column::ExpiresAt.gt(now))
+        .one(&app_state.db)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    let password = hash_blocking(&app_state.password_hasher, &payload.new_password).await?;
+
+    let txn = app_state.db.begin().await?;
+
+    // a token works once, even when it's used twice at the same time
+    let claimed = password_reset_token::Entity::update_many()
+        .col_expr(password_reset_token::Column::UsedAt, Expr::value(now_tz))
+        .filter(password_reset_token::Column::Id.eq(stored_token.id))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .exec(&txn)
+        .await?;
+
+    if claimed.rows_affected == 0 {
+        txn.rollback().await?;
+
+        return Err(invalid_token());
+    }
+
+    let user = user::Entity::find_by_id(stored_token.user_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(invalid_token)?;
+    let user_id = user.id;
+
+    let mut user: user::ActiveModel = user.into();
+    user.password = Set(password);
+    user.failed_login_count = Set(0);
+    user.last_failed_login_at = Set(None);
+    user.locked_until = Set(None);
+    user.update(&txn).await?;
+
+    // whoever knew the old password loses access
+    revocation::revoke_sessions(&txn, user_id, now_tz).await?;
+
+    txn.commit().await?;
+
+    tracing::info!(user_id, "Password reset");
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Password reset successfully".to_string()),
+    ))
+}
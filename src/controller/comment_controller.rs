@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{get, put},
+    Extension, Json, Router,
+};
+use sea_orm::{
+    prelude::Expr, ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, DbErr,
+    EntityTrait, ModelTrait, PaginatorTrait, QueryFilter, Set, TransactionTrait as _,
+};
+use validator::Validate;
+
+use crate::{
+    api_response::JsonResponse,
+    error::AppError,
+    form::comment_form::{CreateCommentRequest, UpdateCommentRequest},
+    models::_entities::{task, task_comment, user},
+    pagination::Pagination,
+    serializer::CommentSerializer,
+    AppState,
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_comments).post(create_comment))
+        .route("/{comment_id}", put(update_comment).delete(delete_comment))
+}
+
+#[axum::debug_handler]
+pub async fn get_comments(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    pagination: Pagination,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+
+    let comment_query = task.find_related(task_comment::Entity);
+
+    let comment_count = comment_query.clone().count(&app_state.db).await?;
+
+    let comment_key = |(comment, _): &(task_comment::Model, Option<user::Model>)| {
+        (comment.date_created, comment.id)
+    };
+
+    let comment_page = pagination
+        .fetch(
+            &app_state.db,
+            comment_query.find_also_related(user::Entity),
+            comment_count,
+            (task_comment::Column::DateCreated, task_comment::Column::Id),
+            false,
+            comment_key,
+        )
+        .await?;
+
+    let response_metadata = pagination.metadata(
+        comment_count,
+        comment_page.next_cursor,
+        comment_page.prev_cursor,
+    );
+
+    let comments: Vec<CommentSerializer> = comment_page
+        .items
+        .into_iter()
+        .map(CommentSerializer::from)
+        .collect();
+
+    Ok(JsonResponse::paginate(comments, response_metadata, None))
+}
+
+#[axum::debug_handler]
+pub async fn create_comment(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+    Json(payload): Json<CreateCommentRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+    let user_id = user_model.id;
+
+    let comment = app_state
+        .db
+        .transaction::<_, task_comment::Model, DbErr>(|txn| {
+            Box::pin(async move {
+                let comment = task_comment::ActiveModel {
+                    id: NotSet,
+                    task_id: Set(task.id),
+                    user_id: Set(user_id),
+                    body: Set(payload.body),
+                    edited: Set(false),
+                    date_created: NotSet,
+                    date_updated: NotSet,
+                }
+                .insert(txn)
+                .await?;
+
+                update_comment_count(txn, task.id, 1).await?;
+
+                Ok(comment)
+            })
+        })
+        .await?;
+
+    Ok(JsonResponse::data(
+        CommentSerializer::from((comment, Some(user_model))),
+        Some("Comment added successfully".to_string()),
+    ))
+}
+
+/// Only the author can change a comment.
+#[axum::debug_handler]
+pub async fn update_comment(
+    State(app_state): State<Arc<AppState>>,
+    Path((task_uuid, comment_id)): Path<(String, i32)>,
+    Extension(user_model): Extension<user::Model>,
+    Json(payload): Json<UpdateCommentRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+    let comment = find_comment(&app_state, &task, comment_id).await?;
+
+    if comment.user_id != user_model.id {
+        return Err(AppError::Forbidden(
+            "Only the author can edit a comment.".to_string(),
+        ));
+    }
+
+    let mut comment: task_comment::ActiveModel = comment.into();
+    comment.body = Set(payload.body);
+    comment.edited = Set(true);
+    comment.date_updated = Set(Some(chrono::Utc::now().into()));
+
+    let comment = comment.update(&app_state.db).await?;
+
+    Ok(JsonResponse::data(
+        CommentSerializer::from((comment, Some(user_model))),
+        None,
+    ))
+}
+
+/// Only the author can remove a comment.
+#[axum::debug_handler]
+pub async fn delete_comment(
+    State(app_state): State<Arc<AppState>>,
+    Path((task_uuid, comment_id)): Path<(String, i32)>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = find_task(&app_state, &user_model, task_uuid).await?;
+    let comment = find_comment(&app_state, &task, comment_id).await?;
+
+    if comment.user_id != user_model.id {
+        return Err(AppError::Forbidden(
+            "Only the author can delete a comment.".to_string(),
+        ));
+    }
+
+    app_state
+        .db
+        .transaction::<_, (), DbErr>(|txn| {
+            Box::pin(async move {
+                comment.delete(txn).await?;
+
+                update_comment_count(txn, task.id, -1).await
+            })
+        })
+        .await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Comment deleted successfully".to_string()),
+    ))
+}
+
+/// Comments are as private as their task, only its owner can read or add them.
+async fn find_task(
+    app_state: &AppState,
+    user_model: &user::Model,
+    task_uuid: String,
+) -> Result<task::Model, AppError> {
+    Ok(user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Task not found.".into()))?)
+}
+
+async fn find_comment(
+    app_state: &AppState,
+    task: &task::Model,
+    comment_id: i32,
+) -> Result<task_comment::Model, AppError> {
+    Ok(task
+        .find_related(task_comment::Entity)
+        .filter(task_comment::Column::Id.eq(comment_id))
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Comment not found.".into()))?)
+}
+
+/// `task.comment_count` is denormalised, it has to change together with the comments.
+async fn update_comment_count<C>(db: &C, task_id: i32, by: i32) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    task::Entity::update_many()
+        .col_expr(
+            task::Column::CommentCount,
+            Expr::col(task::Column::CommentCount).add(by),
+        )
+        .filter(task::Column::Id.eq(task_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
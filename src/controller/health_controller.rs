@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Router};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{ConnectionTrait, DbBackend};
+use serde_json::json;
+
+use crate::{api_response::JsonResponse, AppState};
+
+/// Database backends this build was compiled with, see the `sea-orm` features.
+const DATABASE_BACKENDS: [&str; 2] = ["postgres", "sqlite"];
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/version", get(version))
+}
+
+/// The process is up and serving requests, nothing else is checked.
+pub async fn healthz() -> impl IntoResponse {
+    JsonResponse::data(json!({ "status": "ok" }), None)
+}
+
+/// Whether the app can serve traffic: the database answers and every migration has
+/// been applied. Responds with 503 otherwise so the instance is kept out of rotation.
+pub async fn readyz(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    let database = match app_state.db.ping().await {
+        Ok(()) => "ok".to_string(),
+        Err(err) => {
+            tracing::warn!("Readiness check failed, database unreachable: {}", err);
+            "unreachable".to_string()
+        }
+    };
+
+    let migrations = if database == "ok" {
+        match Migrator::get_pending_migrations(&app_state.db).await {
+            Ok(pending) if pending.is_empty() => "ok".to_string(),
+            Ok(pending) => format!("{} pending", pending.len()),
+            Err(err) => {
+                tracing::warn!("Readiness check failed, migrations unknown: {}", err);
+                "unknown".to_string()
+            }
+        }
+    } else {
+        "unknown".to_string()
+    };
+
+    let ready = database == "ok" && migrations == "ok";
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        JsonResponse::data(
+            json!({
+                "status": if ready { "ready" } else { "not_ready" },
+                "checks": {
+                    "database": database,
+                    "migrations": migrations,
+                },
+            }),
+            None,
+        ),
+    )
+}
+
+pub async fn version(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    let database = match app_state.db.get_database_backend() {
+        DbBackend::Postgres => "postgres",
+        DbBackend::Sqlite => "sqlite",
+        DbBackend::MySql => "mysql",
+    };
+
+    JsonResponse::data(
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "git_hash": option_env!("GIT_HASH").unwrap_or("unknown"),
+            "database": database,
+            "database_backends": DATABASE_BACKENDS,
+        }),
+        None,
+    )
+}
@@ -126,7 +126,7 @@ pub async fn delete_label(
 
     let res = label.delete(&app_state.db).await?;
 
-    println!("{:?}", res);
+    tracing::info!(label_id, rows_affected = res.rows_affected, "Label deleted");
 
     Ok(JsonResponse::data(
         None::<String>,
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, patch, post},
+    Extension, Json, Router,
+};
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, ActiveModelTrait, ActiveValue::NotSet, ModelTrait, Set,
+    TransactionTrait, TryIntoModel,
+};
+use validator::Validate;
+
+use crate::{
+    api_response::JsonResponse,
+    auth::{
+        password::{hash_blocking, verify_blocking},
+        revocation,
+    },
+    controller::auth_controller::issue_user_token,
+    error::AppError,
+    form::user_form::{ChangePasswordRequest, UpdateMeRequest, UpdateProfileRequest},
+    models::_entities::{personal_access_token, user, user_profile},
+    serializer::{UserProfileSerializer, UserWithProfileSerializer},
+    AppState,
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_me).patch(update_me))
+        .route("/profile", patch(update_my_profile))
+        .route("/password", post(change_my_password))
+}
+
+#[axum::debug_handler]
+pub async fn get_me(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let profile = user
+        .find_related(user_profile::Entity)
+        .one(&app_state.db)
+        .await?;
+
+    let user_serializer = UserWithProfileSerializer::from((user, profile));
+
+    Ok(JsonResponse::data(user_serializer, None))
+}
+
+#[axum::debug_handler]
+pub async fn update_me(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+    access_token: Option<Extension<personal_access_token::Model>>,
+    Json(payload): Json<UpdateMeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    // the email is where reset links go, a leaked token mustn't be able to take the account
+    if access_token.is_some() && (payload.username.is_some() || payload.email.is_some()) {
+        return Err(AppError::Forbidden(
+            "Personal access tokens can't change the username or email.".to_string(),
+        ));
+    }
+
+    let profile = user
+        .find_related(user_profile::Entity)
+        .one(&app_state.db)
+        .await?;
+
+    let mut user: user::ActiveModel = user.into();
+
+    if let Some(name) = payload.name {
+        user.name = Set(name);
+    }
+
+    if let Some(username) = payload.username {
+        user.username = Set(username);
+    }
+
+    if let Some(email) = payload.email {
+        user.email = Set(email);
+    }
+
+    let user = user.update(&app_state.db).await?;
+
+    let user_serializer = UserWithProfileSerializer::from((user, profile));
+
+    Ok(JsonResponse::data(user_serializer, None))
+}
+
+#[axum::debug_handler]
+pub async fn update_my_profile(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+    Json(payload): Json<UpdateProfileRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let profile = user
+        .find_related(user_profile::Entity)
+        .one(&app_state.db)
+        .await?;
+
+    // users created outside of register may not have a profile yet
+    let mut profile: user_profile::ActiveModel = match profile {
+        Some(profile) => profile.into(),
+        None => user_profile::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            address: Set(None),
+            mobile_number: Set(None),
+        },
+    };
+
+    if let Some(address) = payload.address {
+        profile.address = Set(Some(address));
+    }
+
+    if let Some(mobile_number) = payload.mobile_number {
+        profile.mobile_number = Set(Some(mobile_number));
+    }
+
+    let profile = profile.save(&app_state.db).await?.try_into_model()?;
+
+    let profile_serializer = UserProfileSerializer::from(profile);
+
+    Ok(JsonResponse::data(profile_serializer, None))
+}
+
+/// Ends every other session of the user, the caller gets a new token pair in return.
+#[axum::debug_handler]
+pub async fn change_my_password(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let password_hasher = &app_state.password_hasher;
+
+    if !verify_blocking(password_hasher, &user.password, &payload.current_password).await? {
+        return Err(AppError::GenericError(
+            "Current password is incorrect.".to_string(),
+        ));
+    }
+
+    let password = hash_blocking(password_hasher, &payload.new_password).await?;
+    let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+
+    let txn = app_state.db.begin().await?;
+
+    let mut user: user::ActiveModel = user.into();
+    user.password = Set(password);
+    let user = user.update(&txn).await?;
+
+    revocation::revoke_sessions(&txn, user.id, now).await?;
+
+    let family = uuid::Uuid::new_v4().to_string();
+    let user_token = issue_user_token(&txn, &app_state.config.auth, &user, family).await?;
+
+    txn.commit().await?;
+
+    Ok(JsonResponse::data(
+        user_token,
+        Some("Password changed successfully".to_string()),
+    ))
+}
@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+
+use crate::{telemetry, AppState};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/metrics", get(get_metrics))
+}
+
+/// Every metric in the Prometheus text format.
+pub async fn get_metrics(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    // request metrics are still worth scraping while the database is down
+    if let Err(err) =
+        telemetry::record_gauges(&app_state.db, app_state.config.database.max_connections).await
+    {
+        tracing::warn!("Cannot refresh database gauges: {}", err);
+    }
+
+    app_state.metrics.run_upkeep();
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app_state.metrics.render(),
+    )
+}
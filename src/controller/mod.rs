@@ -1,4 +1,10 @@
+pub mod attachment_controller;
 pub mod auth_controller;
+pub mod comment_controller;
+pub mod health_controller;
 pub mod label_controller;
+pub mod me_controller;
+pub mod metrics_controller;
 pub mod task_controller;
+pub mod token_controller;
 pub mod user_controller;
@@ -1,80 +1,188 @@
-use std::{clone, collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
 use axum::{
-    extract::{OriginalUri, Path, Query, State},
+    extract::{rejection::QueryRejection, Path, Query, State},
     response::IntoResponse,
-    routing::{get, put},
+    routing::{delete, get, put},
     Extension, Json, Router,
 };
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, EntityTrait, IntoActiveModel, ModelTrait,
-    PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait as _,
+    prelude::{DateTimeWithTimeZone, Expr},
+    sea_query::{Func, NullOrdering, SimpleExpr},
+    ActiveEnum, ActiveModelTrait,
+    ActiveValue::NotSet,
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, IntoSimpleExpr, ModelTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select, Set,
+    TransactionTrait as _,
 };
 use validator::Validate;
 
 use crate::{
-    api_response::{JsonResponse, ResponseMetadata},
+    api_response::JsonResponse,
     error::AppError,
     form::task_form::{
-        CreateTaskRequest, UpdateTaskPriorityRequest, UpdateTaskRequest, UpdateTaskStatusRequest,
+        AddTaskDependencyRequest, CreateTaskRequest, LabelMatch, RecurrenceRequest,
+        SearchTasksQuery, TaskFilterQuery, TaskSort, TaskSortField, UpdateTaskParentRequest,
+        UpdateTaskPriorityRequest, UpdateTaskRequest, UpdateTaskStatusRequest,
+    },
+    models::{
+        _entities::{
+            attachment, label,
+            sea_orm_active_enums::{TaskPriority, TaskStatus},
+            task, task_dependency, task_label, task_series, user,
+        },
+        task::{count_search_hits, find_open_blockers, search},
+    },
+    pagination::Pagination,
+    serializer::{
+        FullTaskSerializer, LabelSerializer, TaskDependenciesSerializer,
+        TaskSearchResultSerializer, TaskSerializer, TaskSeriesSerializer, TaskTreeSerializer,
     },
-    models::_entities::{label, task, task_label, user},
-    serializer::{FullTaskSerializer, LabelSerializer, TaskSerializer},
     AppState,
 };
 
 pub async fn get_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_tasks).post(create_task))
+        .route("/search", get(search_tasks))
         .route(
             "/{task_uuid}",
             get(get_task).put(update_task).delete(delete_task),
         )
         .route("/{task_uuid}/full", get(get_task_full_details))
+        .route("/{task_uuid}/subtasks", get(get_subtasks))
         .route("/{task_uuid}/update_status", put(update_task_status))
         .route("/{task_uuid}/update_priority", put(update_task_priority))
+        .route("/{task_uuid}/update_parent", put(update_task_parent))
+        .route(
+            "/{task_uuid}/dependencies",
+            get(get_task_dependencies).post(add_task_dependency),
+        )
+        .route(
+            "/{task_uuid}/dependencies/{blocker_uuid}",
+            delete(remove_task_dependency),
+        )
+        .route(
+            "/{task_uuid}/recurrence",
+            get(get_task_recurrence)
+                .put(update_task_recurrence)
+                .delete(stop_task_recurrence),
+        )
 }
 
 #[axum::debug_handler]
 pub async fn get_tasks(
     State(app_state): State<Arc<AppState>>,
-    Query(params): Query<HashMap<String, String>>,
-    OriginalUri(original_uri): OriginalUri,
+    query: Result<Query<TaskFilterQuery>, QueryRejection>,
+    pagination: Pagination,
     Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    let mut task_query = user_model.find_related(task::Entity);
+    let Query(params) = query?;
+    params.validate()?;
 
-    if let Some(status) = params.get("status") {
-        task_query = task_query.filter(task::Column::Status.eq(status))
+    if pagination.cursor.is_some() && params.sort.is_some() {
+        return Err(AppError::GenericError(
+            "Cursors can't be combined with sort, they follow the default order.".to_string(),
+        ));
     }
 
-    let page = params
-        .get("page")
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(1);
+    let task_query = filter_tasks(user_model.find_related(task::Entity), &params, &user_model);
 
     let task_count = task_query.clone().count(&app_state.db).await?;
 
-    let response_metadata = ResponseMetadata {
-        count: task_count,
-        per_page: 10,
-        total_page: task_count.div_ceil(10),
-        current_url: Some(original_uri.to_string()),
-        ..Default::default()
+    let task_key = |task: &task::Model| (task.date_created, task.id);
+
+    // with `sort` rows are ordered by it first, cursors follow the default order only
+    let sorted = params.sort.is_some();
+    let task_query = if sorted {
+        sort_tasks(task_query, &params.sort_keys())
+    } else {
+        task_query
     };
 
-    let tasks: Vec<TaskSerializer> = task_query
-        .order_by(task::Column::DateCreated, sea_orm::Order::Desc)
-        .paginate(&app_state.db, 10)
-        .fetch_page(page - 1)
-        .await?
-        .iter()
-        .map(|task| TaskSerializer::from(task.clone()))
+    let mut task_page = pagination
+        .fetch(
+            &app_state.db,
+            task_query,
+            task_count,
+            (task::Column::DateCreated, task::Column::Id),
+            true,
+            task_key,
+        )
+        .await?;
+
+    if sorted {
+        task_page.next_cursor = None;
+        task_page.prev_cursor = None;
+    }
+
+    let response_metadata =
+        pagination.metadata(task_count, task_page.next_cursor, task_page.prev_cursor);
+
+    let tasks: Vec<TaskSerializer> = task_page
+        .items
+        .into_iter()
+        .map(TaskSerializer::from)
         .collect();
 
     Ok(JsonResponse::paginate(tasks, response_metadata, None))
 }
 
+/// Full-text search over the titles, descriptions and comments of the user's tasks,
+/// best matches first.
+pub async fn search_tasks(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<SearchTasksQuery>,
+    pagination: Pagination,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    params.validate()?;
+
+    if pagination.cursor.is_some() {
+        return Err(AppError::GenericError(
+            "Search results are ranked and can only be paged by page number.".to_string(),
+        ));
+    }
+
+    let offset = (pagination.page - 1)
+        .checked_mul(pagination.per_page)
+        // bound as a signed 64-bit number
+        .filter(|offset| i64::try_from(*offset).is_ok())
+        .ok_or_else(|| AppError::GenericError("page is too large.".to_string()))?;
+
+    let hit_count = count_search_hits(&app_state.db, user_model.id, &params.q).await?;
+
+    let response_metadata = pagination.metadata(hit_count, None, None);
+
+    let hits = search(
+        &app_state.db,
+        user_model.id,
+        &params.q,
+        pagination.per_page,
+        offset,
+    )
+    .await?;
+
+    let mut tasks_by_id: HashMap<i32, task::Model> = task::Entity::find()
+        .filter(task::Column::Id.is_in(hits.iter().map(|hit| hit.id)))
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(|task| (task.id, task))
+        .collect();
+
+    let results: Vec<TaskSearchResultSerializer> = hits
+        .into_iter()
+        .filter_map(|hit| {
+            tasks_by_id
+                .remove(&hit.id)
+                .map(|task| TaskSearchResultSerializer::from((task, hit)))
+        })
+        .collect();
+
+    Ok(JsonResponse::paginate(results, response_metadata, None))
+}
+
 #[axum::debug_handler]
 pub async fn create_task(
     State(app_state): State<Arc<AppState>>,
@@ -83,10 +191,41 @@ pub async fn create_task(
 ) -> Result<impl IntoResponse, AppError> {
     payload.validate()?;
 
+    let parent_id = match &payload.parent_uuid {
+        Some(parent_uuid) => Some(
+            user_model
+                .find_related(task::Entity)
+                .filter(task::Column::Uuid.eq(parent_uuid))
+                .one(&app_state.db)
+                .await?
+                .ok_or(sea_orm::DbErr::RecordNotFound(
+                    "Parent task not found.".into(),
+                ))?
+                .id,
+        ),
+        None => None,
+    };
+
     let task_model = app_state
         .db
         .transaction::<_, task::Model, sea_orm::DbErr>(|txn| {
             Box::pin(async move {
+                let series_id = match payload.recurrence {
+                    Some(recurrence) => {
+                        let mut series = task_series::ActiveModel {
+                            user_id: Set(user_model.id),
+                            starts_at: Set(payload
+                                .due_date
+                                .unwrap_or_else(|| chrono::Utc::now().into())),
+                            ..Default::default()
+                        };
+                        series.set_rule(recurrence.into());
+
+                        Some(series.insert(txn).await?.id)
+                    }
+                    None => None,
+                };
+
                 let uuid_v4 = uuid::Uuid::new_v4().to_string();
 
                 let task_model = task::ActiveModel {
@@ -100,6 +239,9 @@ pub async fn create_task(
                     date_created: NotSet,
                     date_updated: NotSet,
                     user_id: Set(user_model.id),
+                    parent_id: Set(parent_id),
+                    series_id: Set(series_id),
+                    comment_count: NotSet,
                 }
                 .insert(txn)
                 .await?;
@@ -126,8 +268,7 @@ pub async fn create_task(
                 Ok(task_model)
             })
         })
-        .await
-        .map_err(|e| AppError::GenericError(e.to_string()))?; // should be database error
+        .await?;
 
     Ok(JsonResponse::data(TaskSerializer::from(task_model), None))
 }
@@ -168,14 +309,56 @@ pub async fn get_task_full_details(
         .map(|label| LabelSerializer::from(label.clone()))
         .collect();
 
+    let blocked_by = task.find_blockers(&app_state.db).await?;
+    let blocks = task.find_blocked_tasks(&app_state.db).await?;
+
+    let recurrence = task
+        .find_related(task_series::Entity)
+        .one(&app_state.db)
+        .await?
+        .map(TaskSeriesSerializer::from);
+
+    let descendants = task.find_descendants(&app_state.db).await?;
+
+    let task_tree = TaskTreeSerializer::build(task, descendants);
+
     let full_task_serializer = FullTaskSerializer {
-        task: TaskSerializer::from(task),
+        task: task_tree.task,
         labels,
+        completion: task_tree.completion,
+        subtasks: task_tree.subtasks,
+        blocked_by: blocked_by.into_iter().map(TaskSerializer::from).collect(),
+        blocks: blocks.into_iter().map(TaskSerializer::from).collect(),
+        recurrence,
     };
 
     Ok(JsonResponse::data(full_task_serializer, None))
 }
 
+pub async fn get_subtasks(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let subtasks: Vec<TaskSerializer> = task::Entity::find()
+        .filter(task::Column::ParentId.eq(task.id))
+        .order_by(task::Column::DateCreated, sea_orm::Order::Asc)
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(TaskSerializer::from)
+        .collect();
+
+    Ok(JsonResponse::data(subtasks, None))
+}
+
 pub async fn update_task(
     State(app_state): State<Arc<AppState>>,
     Path(task_uuid): Path<String>,
@@ -189,6 +372,21 @@ pub async fn update_task(
         .await?
         .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
 
+    app_state
+        .task_workflow
+        .check_transition(task.status, payload.status)?;
+
+    let open_subtasks =
+        subtasks_to_close(&app_state, &task, payload.status, payload.cascade_subtasks).await?;
+
+    if payload.status != task.status {
+        let task_ids: Vec<i32> = std::iter::once(task.id)
+            .chain(open_subtasks.iter().map(|subtask| subtask.id))
+            .collect();
+
+        check_blockers(&app_state.db, &task_ids, payload.status).await?;
+    }
+
     // update labels start
     let assigned_labels: Vec<String> = task
         .find_related(label::Entity)
@@ -230,6 +428,11 @@ pub async fn update_task(
                         .await?;
                 }
 
+                close_subtasks(txn, &open_subtasks).await?;
+
+                let completed =
+                    payload.status == TaskStatus::Done && task.status != TaskStatus::Done;
+
                 let mut task: task::ActiveModel = task.into();
                 task.title = Set(payload.title);
                 task.description = Set(payload.description.unwrap());
@@ -237,11 +440,16 @@ pub async fn update_task(
                 task.due_date = payload.due_date.map_or_else(|| NotSet, |v| Set(Some(v)));
                 task.user_id = Set(user_model.id);
 
-                task.update(txn).await
+                let task = task.update(txn).await?;
+
+                if completed {
+                    task.create_next_occurrence(txn).await?;
+                }
+
+                Ok(task)
             })
         })
-        .await
-        .map_err(|e| AppError::GenericError(e.to_string()))?; // should be database error
+        .await?;
 
     // update labels end
 
@@ -260,9 +468,53 @@ pub async fn delete_task(
         .await?
         .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
 
-    let res = task_model.delete(&app_state.db).await?;
+    let txn = app_state.db.begin().await?;
 
-    println!("{:?}", res);
+    // subtasks go with the task, and so do their attachments, whether or not the
+    // database cascades the delete
+    let task_ids: Vec<i32> = std::iter::once(task_model.id)
+        .chain(
+            task_model
+                .find_descendants(&txn)
+                .await?
+                .iter()
+                .map(|subtask| subtask.id),
+        )
+        .collect();
+
+    let attachments = attachment::Entity::find()
+        .filter(attachment::Column::TaskId.is_in(task_ids.clone()))
+        .all(&txn)
+        .await?;
+
+    attachment::Entity::delete_many()
+        .filter(attachment::Column::TaskId.is_in(task_ids.clone()))
+        .exec(&txn)
+        .await?;
+
+    let res = task::Entity::delete_many()
+        .filter(task::Column::Id.is_in(task_ids))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await?;
+
+    tracing::info!(
+        task_id = task_model.id,
+        rows_affected = res.rows_affected,
+        "Task deleted"
+    );
+
+    // the rows are gone already, a file that can't be removed is only logged
+    for attachment in attachments {
+        if let Err(err) = app_state.storage.delete(&attachment.storage_key).await {
+            tracing::warn!(
+                "Could not remove attachment file {}: {}",
+                attachment.storage_key,
+                err
+            );
+        }
+    }
 
     Ok(JsonResponse::data(
         None::<String>,
@@ -276,19 +528,53 @@ pub async fn update_task_status(
     Extension(user_model): Extension<user::Model>,
     Json(task_request): Json<UpdateTaskStatusRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let mut task: task::ActiveModel = user_model
+    let task = user_model
         .find_related(task::Entity)
         .filter(task::Column::Uuid.eq(task_uuid))
         .one(&app_state.db)
         .await?
-        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?
-        .into();
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
 
-    task.status = Set(task_request.status);
+    app_state
+        .task_workflow
+        .check_transition(task.status, task_request.status)?;
 
-    let task_serializer: TaskSerializer = task.update(&app_state.db).await?.into();
+    let open_subtasks = subtasks_to_close(
+        &app_state,
+        &task,
+        task_request.status,
+        task_request.cascade_subtasks,
+    )
+    .await?;
 
-    Ok(JsonResponse::data(task_serializer, None))
+    if task_request.status != task.status {
+        let task_ids: Vec<i32> = std::iter::once(task.id)
+            .chain(open_subtasks.iter().map(|subtask| subtask.id))
+            .collect();
+
+        check_blockers(&app_state.db, &task_ids, task_request.status).await?;
+    }
+
+    let task_model = app_state
+        .db
+        .transaction::<_, task::Model, sea_orm::DbErr>(|txn| {
+            Box::pin(async move {
+                close_subtasks(txn, &open_subtasks).await?;
+
+                // completing a recurring task schedules its next occurrence
+                if task_request.status == TaskStatus::Done && task.status != TaskStatus::Done {
+                    task.create_next_occurrence(txn).await?;
+                }
+
+                let mut task: task::ActiveModel = task.into();
+                task.status = Set(task_request.status);
+
+                task.update(txn).await
+            })
+        })
+        .await?;
+
+    Ok(JsonResponse::data(TaskSerializer::from(task_model), None))
 }
 
 pub async fn update_task_priority(
@@ -312,12 +598,576 @@ pub async fn update_task_priority(
     Ok(JsonResponse::data(task_serializer, None))
 }
 
+pub async fn update_task_parent(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+    Json(task_request): Json<UpdateTaskParentRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let parent_id = match task_request.parent_uuid {
+        Some(parent_uuid) => {
+            let parent = user_model
+                .find_related(task::Entity)
+                .filter(task::Column::Uuid.eq(parent_uuid))
+                .one(&app_state.db)
+                .await?
+                .ok_or(sea_orm::DbErr::RecordNotFound(
+                    "Parent task not found.".into(),
+                ))?;
+
+            if task.would_create_cycle(&app_state.db, &parent).await? {
+                return Err(AppError::Conflict(
+                    "A task can't be moved below itself or one of its subtasks.".to_string(),
+                ));
+            }
+
+            Some(parent.id)
+        }
+        None => None,
+    };
+
+    let mut task: task::ActiveModel = task.into();
+    task.parent_id = Set(parent_id);
+
+    let task_serializer: TaskSerializer = task.update(&app_state.db).await?.into();
+
+    Ok(JsonResponse::data(task_serializer, None))
+}
+
+pub async fn get_task_dependencies(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let dependencies = TaskDependenciesSerializer {
+        blocked_by: task
+            .find_blockers(&app_state.db)
+            .await?
+            .into_iter()
+            .map(TaskSerializer::from)
+            .collect(),
+        blocks: task
+            .find_blocked_tasks(&app_state.db)
+            .await?
+            .into_iter()
+            .map(TaskSerializer::from)
+            .collect(),
+    };
+
+    Ok(JsonResponse::data(dependencies, None))
+}
+
+pub async fn add_task_dependency(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+    Json(payload): Json<AddTaskDependencyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let blocker = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(payload.blocked_by_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound(
+            "Blocking task not found.".into(),
+        ))?;
+
+    if blocker.id == task.id || blocker.depends_on(&app_state.db, task.id).await? {
+        return Err(AppError::Conflict(
+            "This dependency would create a cycle.".to_string(),
+        ));
+    }
+
+    let existing_dependency = task_dependency::Entity::find()
+        .filter(task_dependency::Column::TaskId.eq(task.id))
+        .filter(task_dependency::Column::BlockedById.eq(blocker.id))
+        .one(&app_state.db)
+        .await?;
+
+    if existing_dependency.is_some() {
+        return Err(AppError::Conflict("Dependency already exists.".to_string()));
+    }
+
+    task_dependency::ActiveModel {
+        id: NotSet,
+        task_id: Set(task.id),
+        blocked_by_id: Set(blocker.id),
+        date_created: NotSet,
+    }
+    .insert(&app_state.db)
+    .await?;
+
+    Ok(JsonResponse::data(
+        TaskSerializer::from(blocker),
+        Some("Dependency added successfully".to_string()),
+    ))
+}
+
+pub async fn remove_task_dependency(
+    State(app_state): State<Arc<AppState>>,
+    Path((task_uuid, blocker_uuid)): Path<(String, String)>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let blocker = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(blocker_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound(
+            "Blocking task not found.".into(),
+        ))?;
+
+    let res = task_dependency::Entity::delete_many()
+        .filter(task_dependency::Column::TaskId.eq(task.id))
+        .filter(task_dependency::Column::BlockedById.eq(blocker.id))
+        .exec(&app_state.db)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Err(sea_orm::DbErr::RecordNotFound("Dependency not found.".into()).into());
+    }
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Dependency removed successfully".to_string()),
+    ))
+}
+
+pub async fn get_task_recurrence(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let series = task
+        .find_related(task_series::Entity)
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound(
+            "Task is not recurring.".into(),
+        ))?;
+
+    Ok(JsonResponse::data(TaskSeriesSerializer::from(series), None))
+}
+
+/// Changes the schedule of the series the task belongs to, or makes the task
+/// recurring when it isn't yet. Occurrences that already exist are left as they are.
+pub async fn update_task_recurrence(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+    Json(payload): Json<RecurrenceRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let series = task
+        .find_related(task_series::Entity)
+        .one(&app_state.db)
+        .await?;
+
+    let series_model = app_state
+        .db
+        .transaction::<_, task_series::Model, sea_orm::DbErr>(|txn| {
+            Box::pin(async move {
+                let Some(series) = series else {
+                    let mut series = task_series::ActiveModel {
+                        user_id: Set(user_model.id),
+                        starts_at: Set(task.due_date.unwrap_or_else(|| chrono::Utc::now().into())),
+                        ..Default::default()
+                    };
+                    series.set_rule(payload.into());
+
+                    let series = series.insert(txn).await?;
+
+                    let mut task: task::ActiveModel = task.into();
+                    task.series_id = Set(Some(series.id));
+                    task.update(txn).await?;
+
+                    return Ok(series);
+                };
+
+                let mut series: task_series::ActiveModel = series.into();
+                series.set_rule(payload.into());
+                series.active = Set(true);
+                series.date_updated = Set(Some(chrono::Utc::now().into()));
+
+                series.update(txn).await
+            })
+        })
+        .await?;
+
+    Ok(JsonResponse::data(
+        TaskSeriesSerializer::from(series_model),
+        None,
+    ))
+}
+
+/// Stops the series, no further occurrences are created. Existing ones are kept.
+pub async fn stop_task_recurrence(
+    State(app_state): State<Arc<AppState>>,
+    Path(task_uuid): Path<String>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = user_model
+        .find_related(task::Entity)
+        .filter(task::Column::Uuid.eq(task_uuid))
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Task not found.".into()))?;
+
+    let series = task
+        .find_related(task_series::Entity)
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound(
+            "Task is not recurring.".into(),
+        ))?;
+
+    let mut series: task_series::ActiveModel = series.into();
+    series.active = Set(false);
+    series.date_updated = Set(Some(chrono::Utc::now().into()));
+
+    let series_model = series.update(&app_state.db).await?;
+
+    Ok(JsonResponse::data(
+        TaskSeriesSerializer::from(series_model),
+        Some("Recurrence stopped successfully".to_string()),
+    ))
+}
+
+/// Narrows `task_query` down to the tasks matching the filters in `params`.
+fn filter_tasks(
+    mut task_query: Select<task::Entity>,
+    params: &TaskFilterQuery,
+    user_model: &user::Model,
+) -> Select<task::Entity> {
+    if let Some(status) = params.status {
+        task_query = task_query.filter(task::Column::Status.eq(status));
+    }
+
+    if let Some(priority) = params.priority {
+        task_query = task_query.filter(task::Column::Priority.eq(priority));
+    }
+
+    if let Some(title) = &params.title_contains {
+        task_query = task_query.filter(task::Column::Title.contains(title));
+    }
+
+    let label_titles = params.label_titles();
+
+    if !label_titles.is_empty() {
+        let mut labelled_tasks = task_label::Entity::find()
+            .select_only()
+            .column(task_label::Column::TaskId)
+            .inner_join(label::Entity)
+            .filter(label::Column::UserId.eq(user_model.id))
+            .filter(label::Column::Title.is_in(label_titles.clone()));
+
+        if params.label_match == LabelMatch::All {
+            labelled_tasks = labelled_tasks.group_by(task_label::Column::TaskId).having(
+                Expr::expr(Func::count_distinct(Expr::col((
+                    label::Entity,
+                    label::Column::Title,
+                ))))
+                .eq(label_titles.len() as i64),
+            );
+        }
+
+        task_query = task_query.filter(task::Column::Id.in_subquery(labelled_tasks.into_query()));
+    }
+
+    if let Some(has_no_labels) = params.has_no_labels {
+        let labelled_tasks = task_label::Entity::find()
+            .select_only()
+            .column(task_label::Column::TaskId)
+            .into_query();
+
+        task_query = if has_no_labels {
+            task_query.filter(task::Column::Id.not_in_subquery(labelled_tasks))
+        } else {
+            task_query.filter(task::Column::Id.in_subquery(labelled_tasks))
+        };
+    }
+
+    for (column, before, after) in [
+        (task::Column::DueDate, params.due_before, params.due_after),
+        (
+            task::Column::DateCreated,
+            params.created_before,
+            params.created_after,
+        ),
+        (
+            task::Column::DateUpdated,
+            params.updated_before,
+            params.updated_after,
+        ),
+    ] {
+        if let Some(before) = before {
+            task_query = task_query.filter(column.lt(before));
+        }
+
+        if let Some(after) = after {
+            task_query = task_query.filter(column.gt(after));
+        }
+    }
+
+    if let Some(overdue) = params.overdue {
+        let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+        let is_overdue = Condition::all()
+            .add(task::Column::DueDate.lt(now))
+            .add(task::Column::Status.ne(TaskStatus::Done));
+
+        task_query = task_query.filter(if overdue {
+            is_overdue
+        } else {
+            // `NOT (due_date < now ...)` is NULL rather than true without a due date
+            Condition::any()
+                .add(task::Column::DueDate.is_null())
+                .add(is_overdue.not())
+        });
+    }
+
+    task_query
+}
+
+/// Orders `task_query` by `sort_keys`, the id breaks ties so pages are stable.
+fn sort_tasks(
+    mut task_query: Select<task::Entity>,
+    sort_keys: &[TaskSort],
+) -> Select<task::Entity> {
+    for sort in sort_keys {
+        let order = if sort.descending {
+            sea_orm::Order::Desc
+        } else {
+            sea_orm::Order::Asc
+        };
+
+        let expr: SimpleExpr = match sort.field {
+            TaskSortField::Title => task::Column::Title.into_simple_expr(),
+            TaskSortField::Status => task::Column::Status.into_simple_expr(),
+            // ranked by urgency rather than alphabetically
+            TaskSortField::Priority => {
+                Expr::case(task::Column::Priority.eq(TaskPriority::Urgent), 3)
+                    .case(task::Column::Priority.eq(TaskPriority::High), 2)
+                    .case(task::Column::Priority.eq(TaskPriority::Medium), 1)
+                    .finally(0)
+                    .into()
+            }
+            TaskSortField::DueDate => task::Column::DueDate.into_simple_expr(),
+            TaskSortField::DateCreated => task::Column::DateCreated.into_simple_expr(),
+            TaskSortField::DateUpdated => task::Column::DateUpdated.into_simple_expr(),
+        };
+
+        // tasks without a due date or update always come last
+        task_query = task_query.order_by_with_nulls(expr, order, NullOrdering::Last);
+    }
+
+    task_query.order_by(task::Column::Id, sea_orm::Order::Desc)
+}
+
+/// The open subtasks of `task` that setting it to `status` would leave behind. A task
+/// is only done over open subtasks with `cascade`, they're then closed along with it
+/// by [`close_subtasks`].
+async fn subtasks_to_close(
+    app_state: &AppState,
+    task: &task::Model,
+    status: TaskStatus,
+    cascade: bool,
+) -> Result<Vec<task::Model>, AppError> {
+    if status != TaskStatus::Done {
+        return Ok(Vec::new());
+    }
+
+    let open_subtasks: Vec<task::Model> = task
+        .find_descendants(&app_state.db)
+        .await?
+        .into_iter()
+        .filter(|subtask| subtask.status != TaskStatus::Done)
+        .collect();
+
+    if !open_subtasks.is_empty() && !cascade {
+        return Err(AppError::Conflict(format!(
+            "Task has {} open subtask(s). Complete them first or set cascade_subtasks.",
+            open_subtasks.len()
+        )));
+    }
+
+    for subtask in &open_subtasks {
+        app_state
+            .task_workflow
+            .check_transition(subtask.status, TaskStatus::Done)?;
+    }
+
+    Ok(open_subtasks)
+}
+
+/// Marks the subtasks from [`subtasks_to_close`] as done, recurring ones get their
+/// next occurrence.
+async fn close_subtasks<C>(txn: &C, subtasks: &[task::Model]) -> Result<(), sea_orm::DbErr>
+where
+    C: ConnectionTrait,
+{
+    if subtasks.is_empty() {
+        return Ok(());
+    }
+
+    task::Entity::update_many()
+        .col_expr(
+            task::Column::Status,
+            Expr::value(TaskStatus::Done.to_value()),
+        )
+        .filter(task::Column::Id.is_in(subtasks.iter().map(|subtask| subtask.id)))
+        .exec(txn)
+        .await?;
+
+    for subtask in subtasks {
+        subtask.create_next_occurrence(txn).await?;
+    }
+
+    Ok(())
+}
+
+/// Tasks can't be started or finished while a task they wait on is still open.
+async fn check_blockers(
+    db: &sea_orm::DatabaseConnection,
+    task_ids: &[i32],
+    status: TaskStatus,
+) -> Result<(), AppError> {
+    if !matches!(status, TaskStatus::InProgress | TaskStatus::Done) {
+        return Ok(());
+    }
+
+    let open_blockers = find_open_blockers(db, task_ids).await?;
+
+    if open_blockers.is_empty() {
+        return Ok(());
+    }
+
+    let blocker_titles: Vec<String> = open_blockers
+        .into_iter()
+        .map(|blocker| format!("'{}'", blocker.title))
+        .collect();
+
+    Err(AppError::Conflict(format!(
+        "Task is blocked by open task(s): {}.",
+        blocker_titles.join(", ")
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::testing;
 
     #[tokio::test]
     async fn hello_world() {
         assert_eq!(1, 1);
     }
+
+    #[tokio::test]
+    async fn not_overdue_includes_tasks_without_a_due_date() {
+        let db = testing::database("sqlite::memory:").await;
+        let owner = testing::user(&db, "ada").await;
+
+        let now = chrono::Utc::now();
+        for (title, status, due_date) in [
+            ("no due date", TaskStatus::Pending, None),
+            (
+                "overdue",
+                TaskStatus::Pending,
+                Some(now - chrono::Duration::days(1)),
+            ),
+            (
+                "done late",
+                TaskStatus::Done,
+                Some(now - chrono::Duration::days(1)),
+            ),
+            (
+                "upcoming",
+                TaskStatus::Pending,
+                Some(now + chrono::Duration::days(1)),
+            ),
+        ] {
+            task::ActiveModel {
+                title: Set(title.to_string()),
+                description: Set(String::new()),
+                status: Set(status),
+                priority: Set(TaskPriority::Low),
+                uuid: Set(title.to_string()),
+                due_date: Set(due_date.map(Into::into)),
+                date_created: Set(now.into()),
+                user_id: Set(owner.id),
+                comment_count: Set(0),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let titles = |overdue| {
+            let mut params = TaskFilterQuery::default();
+            params.overdue = Some(overdue);
+            let query =
+                filter_tasks(task::Entity::find(), &params, &owner).order_by_asc(task::Column::Id);
+            let db = &db;
+
+            async move {
+                query
+                    .all(db)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|task| task.title)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(titles(true).await, ["overdue"]);
+        assert_eq!(
+            titles(false).await,
+            ["no due date", "done late", "upcoming"]
+        );
+    }
 }
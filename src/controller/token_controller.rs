@@ -0,0 +1,124 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use chrono::{Duration, Utc};
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ModelTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use validator::Validate;
+
+use crate::{
+    api_response::JsonResponse,
+    auth::{personal_access_token, scope::Scope},
+    error::AppError,
+    form::user_form::CreateAccessTokenRequest,
+    models::_entities::{personal_access_token as access_token, user},
+    serializer::PersonalAccessTokenSerializer,
+    AppState,
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_tokens).post(create_token))
+        .route("/{token_id}", delete(revoke_token))
+}
+
+/// Tokens of the user that haven't been revoked, newest first.
+#[axum::debug_handler]
+pub async fn get_tokens(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let tokens = user
+        .find_related(access_token::Entity)
+        .filter(access_token::Column::RevokedAt.is_null())
+        .order_by_desc(access_token::Column::DateCreated)
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(PersonalAccessTokenSerializer::from)
+        .collect::<Vec<_>>();
+
+    Ok(JsonResponse::data(tokens, None))
+}
+
+#[axum::debug_handler]
+pub async fn create_token(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<user::Model>,
+    Json(payload): Json<CreateAccessTokenRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let mut seen = HashSet::new();
+    let scopes: Vec<Scope> = payload
+        .scopes
+        .into_iter()
+        .filter(|scope| seen.insert(*scope))
+        .collect();
+
+    let expires_at: Option<DateTimeWithTimeZone> = payload
+        .expires_in_days
+        .map(|days| (Utc::now() + Duration::days(days)).into());
+
+    let generated = personal_access_token::generate();
+
+    let created = access_token::ActiveModel {
+        id: NotSet,
+        user_id: Set(user.id),
+        name: Set(payload.name),
+        token_prefix: Set(generated.prefix),
+        token_hash: Set(generated.hash),
+        scopes: Set(Scope::join(&scopes)),
+        expires_at: Set(expires_at),
+        last_used_at: NotSet,
+        revoked_at: NotSet,
+        date_created: NotSet,
+    }
+    .insert(&app_state.db)
+    .await?;
+
+    tracing::info!(token_id = created.id, "Personal access token created");
+
+    let token_serializer = PersonalAccessTokenSerializer {
+        token: Some(generated.token),
+        ..PersonalAccessTokenSerializer::from(created)
+    };
+
+    Ok(JsonResponse::data(
+        token_serializer,
+        Some("Token created successfully".to_string()),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn revoke_token(
+    State(app_state): State<Arc<AppState>>,
+    Path(token_id): Path<i32>,
+    Extension(user): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = user
+        .find_related(access_token::Entity)
+        .filter(access_token::Column::Id.eq(token_id))
+        .filter(access_token::Column::RevokedAt.is_null())
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("Token not found.".into()))?;
+
+    let mut token: access_token::ActiveModel = token.into();
+    token.revoked_at = Set(Some(Utc::now().into()));
+    token.update(&app_state.db).await?;
+
+    tracing::info!(token_id, "Personal access token revoked");
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Token revoked successfully".to_string()),
+    ))
+}
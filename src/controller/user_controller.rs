@@ -2,39 +2,47 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use axum::{
-    extract::{OriginalUri, Path, Query, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
 use sea_orm::{
     ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, DbErr, EntityTrait, ModelTrait,
-    PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+    PaginatorTrait, QueryFilter, Set, TransactionTrait,
 };
 use validator::Validate;
 
-use crate::api_response::{JsonResponse, ResponseMetadata};
+use crate::api_response::JsonResponse;
+use crate::auth::{password::hash_blocking, revocation};
 use crate::error::AppError;
-use crate::form::user_form::{CreateUserRequest, UpdateUserRequest};
-use crate::models::_entities::{task, user, user_profile};
-use crate::serializer::{TaskSerializer, UserSerializer, UserWithProfileSerializer};
+use crate::form::user_form::{CreateUserRequest, LoginAttemptQuery, UpdateUserRequest};
+use crate::middlewares::role_guard::admin_guard;
+use crate::models::_entities::{login_attempt, task, user, user_profile};
+use crate::pagination::Pagination;
+use crate::serializer::{
+    LoginAttemptSerializer, TaskSerializer, UserSerializer, UserWithProfileSerializer,
+};
 use crate::AppState;
 
 pub async fn get_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_users).post(create_user))
+        .route("/login_attempts", get(get_login_attempts))
         .route(
             "/{user_id}",
             get(get_user).put(update_user).delete(delete_user),
         )
         .route("/{user_id}/tasks", get(get_user_tasks))
+        .route("/{user_id}/unlock", post(unlock_user))
+        .route_layer(axum::middleware::from_fn(admin_guard))
 }
 
 #[axum::debug_handler()]
 pub async fn get_users(
     State(app_state): State<Arc<AppState>>,
     Query(params): Query<HashMap<String, String>>,
-    OriginalUri(original_uri): OriginalUri,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, AppError> {
     let mut user_query = user::Entity::find().find_also_related(user_profile::Entity);
 
@@ -50,28 +58,29 @@ pub async fn get_users(
         user_query = user_query.filter(user::Column::Email.contains(email));
     }
 
-    let page = params
-        .get("page")
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(1);
-
     let users_count = user_query.clone().count(&app_state.db).await?;
 
-    let response_metadata = ResponseMetadata {
-        count: users_count,
-        per_page: 10,
-        total_page: users_count.div_ceil(10),
-        current_url: Some(original_uri.to_string()),
-        ..Default::default()
-    };
+    let user_key =
+        |(user, _): &(user::Model, Option<user_profile::Model>)| (user.date_created, user.id);
+
+    let user_page = pagination
+        .fetch(
+            &app_state.db,
+            user_query,
+            users_count,
+            (user::Column::DateCreated, user::Column::Id),
+            true,
+            user_key,
+        )
+        .await?;
 
-    let users: Vec<UserWithProfileSerializer> = user_query
-        .order_by(user::Column::DateCreated, sea_orm::Order::Desc)
-        .paginate(&app_state.db, 10)
-        .fetch_page(page - 1)
-        .await?
-        .iter()
-        .map(|user_with_profile| UserWithProfileSerializer::from(user_with_profile.clone()))
+    let response_metadata =
+        pagination.metadata(users_count, user_page.next_cursor, user_page.prev_cursor);
+
+    let users: Vec<UserWithProfileSerializer> = user_page
+        .items
+        .into_iter()
+        .map(UserWithProfileSerializer::from)
         .collect();
 
     Ok(JsonResponse::paginate(users, response_metadata, None))
@@ -99,13 +108,16 @@ pub async fn create_user(
 ) -> Result<impl IntoResponse, AppError> {
     user_request.validate()?;
 
+    let user = user_request
+        .clone()
+        .into_active_model(&app_state.password_hasher)
+        .await?;
+
     let user_with_profile = app_state
         .db
         .transaction::<_, (user::Model, Option<user_profile::Model>), DbErr>(|txn| {
             Box::pin(async move {
-                let user = user::ActiveModel::from(user_request.clone())
-                    .insert(txn)
-                    .await?;
+                let user = user.insert(txn).await?;
 
                 let user_profile = user_profile::ActiveModel {
                     id: sea_orm::ActiveValue::NotSet,
@@ -119,8 +131,7 @@ pub async fn create_user(
                 Ok((user, Some(user_profile)))
             })
         })
-        .await
-        .map_err(|e| AppError::GenericError(e.to_string()))?; // should be database error
+        .await?;
 
     let user_serializer = UserWithProfileSerializer::from(user_with_profile);
 
@@ -140,10 +151,13 @@ pub async fn update_user(
 
     user_request.validate()?;
 
+    // whoever had the old password or could reset it through the old email loses access
+    let credentials_changed = user_request.password.is_some() || user_request.email != user.email;
+
     let mut user: user::ActiveModel = user.into();
 
     let password = match user_request.password {
-        Some(pwd) => Set(pwd),
+        Some(pwd) => Set(hash_blocking(&app_state.password_hasher, &pwd).await?),
         None => NotSet,
     };
 
@@ -152,7 +166,21 @@ pub async fn update_user(
     user.email = Set(user_request.email);
     user.password = password;
 
-    let user_serializer: UserSerializer = user.update(&app_state.db).await?.into();
+    if let Some(role) = user_request.role {
+        user.role = Set(role);
+    }
+
+    let txn = app_state.db.begin().await?;
+
+    let user = user.update(&txn).await?;
+
+    if credentials_changed {
+        revocation::revoke_sessions(&txn, user.id, chrono::Utc::now().into()).await?;
+    }
+
+    txn.commit().await?;
+
+    let user_serializer: UserSerializer = user.into();
 
     Ok(JsonResponse::data(user_serializer, None))
 }
@@ -166,7 +194,7 @@ pub async fn delete_user(
         .exec(&app_state.db)
         .await?;
 
-    println!("{:?}", res);
+    tracing::info!(user_id, rows_affected = res.rows_affected, "User deleted");
 
     Ok(JsonResponse::data(
         None::<String>,
@@ -178,8 +206,7 @@ pub async fn delete_user(
 pub async fn get_user_tasks(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Query(params): Query<HashMap<String, String>>,
-    OriginalUri(original_uri): OriginalUri,
+    pagination: Pagination,
 ) -> Result<impl IntoResponse, AppError> {
     let user = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
@@ -190,24 +217,26 @@ pub async fn get_user_tasks(
 
     let task_count = task_query.clone().count(&app_state.db).await?;
 
-    let response_metadata = ResponseMetadata::new(task_count, Some(original_uri.to_string()));
+    let task_key = |task: &task::Model| (task.date_created, task.id);
 
-    let page = params
-        .get("page")
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(1);
+    let task_page = pagination
+        .fetch(
+            &app_state.db,
+            task_query,
+            task_count,
+            (task::Column::DateCreated, task::Column::Id),
+            true,
+            task_key,
+        )
+        .await?;
 
-    let per_page = std::env::var("PER_PAGE")
-        .ok()
-        .and_then(|v| v.parse::<u64>().ok())
-        .unwrap_or(10);
+    let response_metadata =
+        pagination.metadata(task_count, task_page.next_cursor, task_page.prev_cursor);
 
-    let task_serializer: Vec<TaskSerializer> = task_query
-        .paginate(&app_state.db, per_page)
-        .fetch_page(page - 1)
-        .await?
-        .iter()
-        .map(|task| TaskSerializer::from(task.clone()))
+    let task_serializer: Vec<TaskSerializer> = task_page
+        .items
+        .into_iter()
+        .map(TaskSerializer::from)
         .collect();
 
     Ok(JsonResponse::paginate(
@@ -216,3 +245,234 @@ pub async fn get_user_tasks(
         None,
     ))
 }
+
+/// Lifts a lockout and forgets the failed logins that led to it.
+#[axum::debug_handler()]
+pub async fn unlock_user(
+    State(app_state): State<Arc<AppState>>,
+    Path(user_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = user::Entity::find_by_id(user_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(sea_orm::DbErr::RecordNotFound("User not found.".into()))?;
+
+    let mut user: user::ActiveModel = user.into();
+    user.failed_login_count = Set(0);
+    user.last_failed_login_at = Set(None);
+    user.locked_until = Set(None);
+
+    let user_serializer: UserSerializer = user.update(&app_state.db).await?.into();
+
+    tracing::info!(user_id, "User unlocked");
+
+    Ok(JsonResponse::data(
+        user_serializer,
+        Some("User unlocked successfully".to_string()),
+    ))
+}
+
+/// Every login attempt, newest first, for auditing.
+#[axum::debug_handler()]
+pub async fn get_login_attempts(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<LoginAttemptQuery>,
+    pagination: Pagination,
+) -> Result<impl IntoResponse, AppError> {
+    let mut attempt_query = login_attempt::Entity::find();
+
+    if let Some(username) = params.username {
+        attempt_query = attempt_query.filter(login_attempt::Column::Username.eq(username));
+    }
+
+    if let Some(user_id) = params.user_id {
+        attempt_query = attempt_query.filter(login_attempt::Column::UserId.eq(user_id));
+    }
+
+    if let Some(succeeded) = params.succeeded {
+        attempt_query = attempt_query.filter(login_attempt::Column::Succeeded.eq(succeeded));
+    }
+
+    let attempt_count = attempt_query.clone().count(&app_state.db).await?;
+
+    let attempt_key = |attempt: &login_attempt::Model| (attempt.date_created, attempt.id);
+
+    let attempt_page = pagination
+        .fetch(
+            &app_state.db,
+            attempt_query,
+            attempt_count,
+            (
+                login_attempt::Column::DateCreated,
+                login_attempt::Column::Id,
+            ),
+            true,
+            attempt_key,
+        )
+        .await?;
+
+    let response_metadata = pagination.metadata(
+        attempt_count,
+        attempt_page.next_cursor,
+        attempt_page.prev_cursor,
+    );
+
+    let attempts: Vec<LoginAttemptSerializer> = attempt_page
+        .items
+        .into_iter()
+        .map(LoginAttemptSerializer::from)
+        .collect();
+
+    Ok(JsonResponse::paginate(attempts, response_metadata, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::{create_user_token, TokenClaims, TokenType};
+    use crate::config::AppConfig;
+    use crate::models::_entities::{
+        personal_access_token, refresh_token, sea_orm_active_enums::UserRole,
+    };
+    use crate::models::testing;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use chrono::{Duration, Utc};
+    use sea_orm::DatabaseConnection;
+    use tower::ServiceExt;
+
+    /// A user with a refresh token and a personal access token.
+    async fn signed_in_user(db: &DatabaseConnection, username: &str) -> user::Model {
+        let user = testing::user(db, username).await;
+        let now = Utc::now();
+
+        refresh_token::ActiveModel {
+            jti: Set(format!("{}-jti", username)),
+            family: Set("family".to_string()),
+            user_id: Set(user.id),
+            expires_at: Set((now + Duration::days(1)).into()),
+            date_created: Set(now.into()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+
+        personal_access_token::ActiveModel {
+            user_id: Set(user.id),
+            name: Set("ci".to_string()),
+            token_prefix: Set("prefix".to_string()),
+            token_hash: Set(format!("{}-hash", username)),
+            scopes: Set("tasks:read".to_string()),
+            date_created: Set(now.into()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+
+        user
+    }
+
+    /// Whether the cutoff is set and the refresh and personal access token are revoked.
+    async fn is_signed_out(db: &DatabaseConnection, user_id: i32) -> [bool; 3] {
+        let user = user::Entity::find_by_id(user_id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        let refresh_token = refresh_token::Entity::find()
+            .filter(refresh_token::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        let personal_access_token = personal_access_token::Entity::find()
+            .filter(personal_access_token::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        [
+            user.token_valid_after.is_some(),
+            refresh_token.revoked_at.is_some(),
+            personal_access_token.revoked_at.is_some(),
+        ]
+    }
+
+    #[tokio::test]
+    async fn changing_the_password_or_email_signs_the_user_out() {
+        let mut config = AppConfig::default();
+        config.database.url = "sqlite:file:update_user?mode=memory&cache=shared".to_string();
+        config.auth.jwt_secret = "test-secret".to_string();
+
+        let db = testing::database(&config.database.url).await;
+
+        let mut admin: user::ActiveModel = testing::user(&db, "admin").await.into();
+        admin.role = Set(UserRole::Admin);
+        let admin = admin.update(&db).await.unwrap();
+
+        let claims = TokenClaims::new(&admin.id.to_string(), TokenType::Access, "test", 5);
+        let token = create_user_token(&claims, &config.auth.jwt_secret).await;
+
+        let app = crate::create_app(config).await;
+
+        let update = |user: &user::Model, body: serde_json::Value| {
+            let request = Request::builder()
+                .method("PUT")
+                .uri(format!("/api/users/{}", user.id))
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+
+            app.clone().oneshot(request)
+        };
+
+        let ada = signed_in_user(&db, "ada").await;
+        let bob = signed_in_user(&db, "bob").await;
+
+        let response = update(
+            &ada,
+            serde_json::json!({
+                "name": "Ada Lovelace",
+                "username": "ada",
+                "email": "ada@example.com",
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(is_signed_out(&db, ada.id).await, [false; 3]);
+
+        let response = update(
+            &ada,
+            serde_json::json!({
+                "name": "Ada Lovelace",
+                "username": "ada",
+                "email": "ada@example.org",
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(is_signed_out(&db, ada.id).await, [true; 3]);
+
+        let response = update(
+            &bob,
+            serde_json::json!({
+                "name": "bob",
+                "username": "bob",
+                "email": "bob@example.com",
+                "password": "a new password",
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(is_signed_out(&db, bob.id).await, [true; 3]);
+    }
+}
@@ -1,7 +1,13 @@
+use std::collections::{BTreeMap, HashMap};
+
 use axum::{
-    http::StatusCode,
+    extract::rejection::QueryRejection,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
+use serde::Serialize;
+use serde_json::{json, Value};
+use validator::{ValidationErrors, ValidationErrorsKind};
 
 use crate::api_response::JsonResponse;
 
@@ -11,6 +17,14 @@ pub enum AppError {
     SeaOrm(sea_orm::DbErr),
     Validation(validator::ValidationErrors),
     Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    PayloadTooLarge(String),
+    /// Seconds until the rate limit allows another request.
+    TooManyRequests(u64),
+    Storage(std::io::Error),
+    /// A failure on our side the client can't do anything about, only logged.
+    Internal(String),
 }
 
 impl From<sea_orm::DbErr> for AppError {
@@ -19,41 +33,459 @@ impl From<sea_orm::DbErr> for AppError {
     }
 }
 
+impl From<sea_orm::TransactionError<sea_orm::DbErr>> for AppError {
+    fn from(value: sea_orm::TransactionError<sea_orm::DbErr>) -> Self {
+        match value {
+            sea_orm::TransactionError::Connection(err) => Self::SeaOrm(err),
+            sea_orm::TransactionError::Transaction(err) => Self::SeaOrm(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Storage(value)
+    }
+}
+
 impl From<validator::ValidationErrors> for AppError {
     fn from(value: validator::ValidationErrors) -> Self {
         Self::Validation(value)
     }
 }
 
+impl From<QueryRejection> for AppError {
+    fn from(value: QueryRejection) -> Self {
+        Self::GenericError(value.body_text())
+    }
+}
+
+/// How an error is presented to the client. Rendered as the usual JSON error body, or
+/// as `application/problem+json` by the `problem_json` middleware when the client asks
+/// for it, which is why it's also attached to the response.
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub detail: String,
+    /// Failed rules per field, for validation errors and unique violations.
+    pub errors: Option<BTreeMap<String, Vec<FieldError>>>,
+    /// Ties the response to the log lines of the error, the request id when there is one.
+    pub correlation_id: String,
+    /// Seconds the client should wait before retrying, sent as `Retry-After`.
+    pub retry_after: Option<u64>,
+}
+
+impl ErrorDetails {
+    fn new(status: StatusCode, code: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            detail: detail.into(),
+            errors: None,
+            correlation_id: crate::middlewares::request_id::current()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            retry_after: None,
+        }
+    }
+
+    /// A 500 that only tells the client where to look in the logs.
+    fn internal(error: &dyn std::fmt::Debug) -> Self {
+        let details = Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Something went wrong on our side.",
+        );
+        tracing::error!(correlation_id = %details.correlation_id, "Internal error {:#?}", error);
+        details
+    }
+
+    fn unavailable(error: &dyn std::fmt::Debug) -> Self {
+        let mut details = Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "service_unavailable",
+            "The database is unavailable, try again shortly.",
+        );
+        details.retry_after = Some(5);
+        tracing::error!(correlation_id = %details.correlation_id, "Database unavailable {:#?}", error);
+        details
+    }
+
+    /// The RFC 7807 representation of the error.
+    pub fn problem(&self) -> Value {
+        let mut problem = json!({
+            "type": "about:blank",
+            "title": self.status.canonical_reason().unwrap_or("Error"),
+            "status": self.status.as_u16(),
+            "detail": self.detail,
+            "code": self.code,
+            "correlation_id": self.correlation_id,
+        });
+
+        if let Some(errors) = &self.errors {
+            problem["errors"] = json!(errors);
+        }
+
+        problem
+    }
+}
+
+impl From<AppError> for ErrorDetails {
+    fn from(value: AppError) -> Self {
+        match value {
+            AppError::GenericError(message) => {
+                Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+            }
+            AppError::SeaOrm(db_err) => Self::from(db_err),
+            AppError::Validation(validation_errors) => {
+                let mut fields = BTreeMap::new();
+                collect_field_errors(&validation_errors, None, &mut fields);
+
+                let mut details = Self::new(
+                    StatusCode::BAD_REQUEST,
+                    "validation_failed",
+                    "Validation failed.",
+                );
+                details.errors = Some(fields);
+                details
+            }
+            AppError::Unauthorized(message) => {
+                Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+            }
+            AppError::Forbidden(message) => Self::new(StatusCode::FORBIDDEN, "forbidden", message),
+            AppError::Conflict(message) => Self::new(StatusCode::CONFLICT, "conflict", message),
+            AppError::PayloadTooLarge(message) => {
+                Self::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", message)
+            }
+            AppError::TooManyRequests(retry_after) => {
+                let mut details = Self::new(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "rate_limited",
+                    "Too many requests, try again later.",
+                );
+                details.retry_after = Some(retry_after);
+                details
+            }
+            AppError::Storage(err) => match err.kind() {
+                std::io::ErrorKind::NotFound => {
+                    Self::new(StatusCode::NOT_FOUND, "not_found", "File not found.")
+                }
+                _ => Self::internal(&err),
+            },
+            AppError::Internal(message) => Self::internal(&message),
+        }
+    }
+}
+
+impl From<sea_orm::DbErr> for ErrorDetails {
+    fn from(db_err: sea_orm::DbErr) -> Self {
+        use sea_orm::{DbErr, RuntimeErr, SqlxError};
+
+        match db_err {
+            DbErr::RecordNotFound(message) => {
+                Self::new(StatusCode::NOT_FOUND, "not_found", message)
+            }
+            DbErr::RecordNotUpdated => {
+                Self::new(StatusCode::NOT_FOUND, "not_found", "Record not found.")
+            }
+            DbErr::RecordNotInserted => {
+                Self::new(StatusCode::CONFLICT, "conflict", "Record already exists.")
+            }
+            DbErr::ConnectionAcquire(_) | DbErr::Conn(_) => Self::unavailable(&db_err),
+            DbErr::Exec(RuntimeErr::SqlxError(ref error))
+            | DbErr::Query(RuntimeErr::SqlxError(ref error)) => match error {
+                SqlxError::Database(database_error) => {
+                    Self::from_database_error(database_error.as_ref())
+                }
+                SqlxError::RowNotFound => {
+                    Self::new(StatusCode::NOT_FOUND, "not_found", "Record not found.")
+                }
+                SqlxError::PoolTimedOut
+                | SqlxError::PoolClosed
+                | SqlxError::WorkerCrashed
+                | SqlxError::Io(_)
+                | SqlxError::Tls(_) => Self::unavailable(&db_err),
+                _ => Self::internal(&db_err),
+            },
+            DbErr::Exec(RuntimeErr::Internal(_))
+            | DbErr::Query(RuntimeErr::Internal(_))
+            | DbErr::TryIntoErr { .. }
+            | DbErr::ConvertFromU64(_)
+            | DbErr::UnpackInsertId
+            | DbErr::UpdateGetPrimaryKey
+            | DbErr::AttrNotSet(_)
+            | DbErr::Custom(_)
+            | DbErr::Type(_)
+            | DbErr::Json(_)
+            | DbErr::Migration(_) => Self::internal(&db_err),
+        }
+    }
+}
+
+impl ErrorDetails {
+    /// Constraint violations are the client's fault, anything else the database
+    /// reports is ours. Raw messages are only logged, they contain SQL and values.
+    fn from_database_error(error: &dyn sea_orm::sqlx::error::DatabaseError) -> Self {
+        use sea_orm::sqlx::error::ErrorKind;
+
+        let mut details = match error.kind() {
+            ErrorKind::UniqueViolation => {
+                let mut details = Self::new(
+                    StatusCode::CONFLICT,
+                    "unique_violation",
+                    "A record with the same values already exists.",
+                );
+                details.errors = unique_violation_field(error).map(|field| {
+                    BTreeMap::from([(
+                        field,
+                        vec![FieldError {
+                            code: "unique".to_string(),
+                            message: "Is already taken".to_string(),
+                            params: HashMap::new(),
+                        }],
+                    )])
+                });
+                details
+            }
+            ErrorKind::ForeignKeyViolation => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "foreign_key_violation",
+                "A referenced record does not exist or is still referenced.",
+            ),
+            ErrorKind::NotNullViolation | ErrorKind::CheckViolation => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "constraint_violation",
+                "The record violates a database constraint.",
+            ),
+            _ => return Self::internal(&error),
+        };
+
+        tracing::warn!(
+            correlation_id = %details.correlation_id,
+            constraint = ?error.constraint(),
+            "Constraint violation {}",
+            error.message()
+        );
+
+        details.detail = match &details.errors {
+            Some(errors) => format!(
+                "A record with the same {} already exists.",
+                errors.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            None => details.detail,
+        };
+
+        details
+    }
+}
+
+/// The column a unique violation is about, from Postgres' `Key (email)=(...)` detail or
+/// SQLite's `UNIQUE constraint failed: user.email` message.
+fn unique_violation_field(error: &dyn sea_orm::sqlx::error::DatabaseError) -> Option<String> {
+    if let Some(pg_error) = error.try_downcast_ref::<sea_orm::SqlxPostgresError>() {
+        return pg_error
+            .detail()
+            .and_then(|detail| detail.strip_prefix("Key ("))
+            .and_then(|detail| detail.split_once(")=("))
+            .map(|(columns, _)| columns.to_string());
+    }
+
+    error
+        .message()
+        .split_once("UNIQUE constraint failed: ")
+        .map(|(_, columns)| {
+            columns
+                .split(", ")
+                .map(|column| column.rsplit('.').next().unwrap_or(column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status_code, error_message) = match self {
-            AppError::GenericError(e) => (StatusCode::BAD_REQUEST, e),
-            AppError::SeaOrm(db_err) => match db_err {
-                sea_orm::DbErr::RecordNotFound(message) => (StatusCode::NOT_FOUND, message),
-                sea_orm::DbErr::Exec(runtime_err) => match runtime_err {
-                    sea_orm::RuntimeErr::SqlxError(error) => match error {
-                        sea_orm::SqlxError::Database(e) => {
-                            tracing::error!("Error {:#?}", e);
-                            tracing::error!("Source {:#?}", e.constraint());
-                            (StatusCode::BAD_REQUEST, e.to_string())
+        ErrorDetails::from(self).into_response()
+    }
+}
+
+impl IntoResponse for ErrorDetails {
+    fn into_response(self) -> Response {
+        let message = match self.errors {
+            Some(_) => self.detail.clone(),
+            None => "Error".to_string(),
+        };
+        let error = match &self.errors {
+            Some(errors) => json!(errors),
+            None => json!(self.detail),
+        };
+
+        let mut response = (
+            self.status,
+            JsonResponse::error(self.code, error, Some(message), &self.correlation_id),
+        )
+            .into_response();
+
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// One failed rule of a field, e.g. `{"code": "length", "message": "...", "params":
+/// {"min": 3}}`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldError {
+    pub code: String,
+    pub message: String,
+    pub params: HashMap<String, Value>,
+}
+
+/// Flattens `errors` into `fields`, keyed by the path of the field. Nested structs are
+/// joined with dots and list items get their index, e.g. `recurrence.interval` or
+/// `items[2].title`, so frontends can point at the exact input.
+fn collect_field_errors(
+    errors: &ValidationErrors,
+    prefix: Option<&str>,
+    fields: &mut BTreeMap<String, Vec<FieldError>>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field),
+            None => field.to_string(),
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                fields
+                    .entry(path)
+                    .or_default()
+                    .extend(field_errors.iter().map(|error| {
+                        FieldError {
+                            code: error.code.to_string(),
+                            message: error
+                                .message
+                                .as_ref()
+                                .map(|message| message.to_string())
+                                .unwrap_or_else(|| default_message(&error.code).to_string()),
+                            params: error
+                                .params
+                                .iter()
+                                // the submitted value could be a password, it's never echoed
+                                .filter(|(name, _)| *name != "value")
+                                .map(|(name, value)| (name.to_string(), value.clone()))
+                                .collect(),
                         }
-                        _ => (StatusCode::INTERNAL_SERVER_ERROR, "Error".into()),
-                    },
-                    sea_orm::RuntimeErr::Internal(_) => todo!(),
-                },
-                _ => (StatusCode::NOT_FOUND, db_err.to_string()),
-            },
-            AppError::Validation(validation_errors) => {
-                (StatusCode::BAD_REQUEST, validation_errors.to_string())
+                    }));
             }
-            AppError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message),
+            ValidationErrorsKind::Struct(nested) => {
+                collect_field_errors(nested, Some(&path), fields);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(nested, Some(&format!("{}[{}]", path, index)), fields);
+                }
+            }
+        }
+    }
+}
+
+fn default_message(code: &str) -> &'static str {
+    match code {
+        "required" => "This field is required",
+        "email" => "Must be a valid email address",
+        "url" => "Must be a valid URL",
+        "length" => "Has an invalid length",
+        "range" => "Is out of range",
+        "must_match" => "Does not match",
+        _ => "Is invalid",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::{Validate, ValidationError};
+
+    use super::*;
+
+    #[derive(Validate)]
+    struct Recurrence {
+        #[validate(range(min = 1, message = "Must be at least 1"))]
+        interval: u32,
+    }
+
+    #[derive(Validate)]
+    struct Form {
+        #[validate(length(min = 8))]
+        password: String,
+        #[validate(nested)]
+        recurrence: Recurrence,
+    }
+
+    #[test]
+    fn nested_errors_are_keyed_by_path() {
+        let form = Form {
+            password: "secret".to_string(),
+            recurrence: Recurrence { interval: 0 },
         };
 
-        (
-            status_code,
-            JsonResponse::error(error_message, Some("Error".to_string())),
-        )
-            .into_response()
+        let mut fields = BTreeMap::new();
+        collect_field_errors(&form.validate().unwrap_err(), None, &mut fields);
+
+        assert_eq!(
+            fields["recurrence.interval"],
+            vec![FieldError {
+                code: "range".to_string(),
+                message: "Must be at least 1".to_string(),
+                params: HashMap::from([("min".to_string(), json!(1))]),
+            }]
+        );
+
+        let password = &fields["password"][0];
+        assert_eq!(password.code, "length");
+        assert_eq!(password.message, "Has an invalid length");
+        assert!(!password.params.contains_key("value"));
+    }
+
+    #[test]
+    fn internals_are_not_sent_to_the_client() {
+        let details = ErrorDetails::from(AppError::SeaOrm(sea_orm::DbErr::Exec(
+            sea_orm::RuntimeErr::Internal("syntax error near SELECT".to_string()),
+        )));
+
+        assert_eq!(details.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!details.detail.contains("SELECT"));
+
+        let details = ErrorDetails::from(AppError::SeaOrm(sea_orm::DbErr::ConnectionAcquire(
+            sea_orm::ConnAcquireErr::Timeout,
+        )));
+
+        assert_eq!(details.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(details.retry_after, Some(5));
+        assert_eq!(
+            details.problem()["correlation_id"],
+            json!(details.correlation_id)
+        );
+
+        let details = ErrorDetails::from(AppError::Internal("invalid Argon2 params".to_string()));
+
+        assert_eq!(details.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!details.detail.contains("Argon2"));
+    }
+
+    #[test]
+    fn custom_errors_keep_their_code() {
+        let mut errors = ValidationErrors::new();
+        errors.add("sort", ValidationError::new("invalid_sort"));
+
+        let mut fields = BTreeMap::new();
+        collect_field_errors(&errors, None, &mut fields);
+
+        assert_eq!(fields["sort"][0].code, "invalid_sort");
+        assert_eq!(fields["sort"][0].message, "Is invalid");
     }
 }
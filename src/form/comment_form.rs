@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct CreateCommentRequest {
+    #[validate(length(min = 1, max = 5000, message = "Must be between 1 and 5000 characters"))]
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct UpdateCommentRequest {
+    #[validate(length(min = 1, max = 5000, message = "Must be between 1 and 5000 characters"))]
+    pub body: String,
+}
@@ -1,3 +1,4 @@
+pub mod comment_form;
 pub mod label_form;
 pub mod task_form;
 pub mod user_form;
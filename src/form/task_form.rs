@@ -1,17 +1,28 @@
+use chrono::Weekday;
 use sea_orm::prelude::DateTimeWithTimeZone;
 
-use serde::{Deserialize, Serialize};
-use validator::Validate;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use crate::{
+    models::_entities::sea_orm_active_enums::{RecurrenceFrequency, TaskPriority, TaskStatus},
+    recurrence::RecurrenceRule,
+};
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateTaskRequest {
     #[validate(length(min = 3, message = "Must have at least 3 characters"))]
     pub title: String,
     pub description: String,
-    pub status: String,
-    pub priority: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub priority: TaskPriority,
     pub due_date: Option<DateTimeWithTimeZone>,
     pub labels: Vec<String>,
+    pub parent_uuid: Option<String>,
+    #[validate(nested)]
+    pub recurrence: Option<RecurrenceRequest>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Validate)]
@@ -19,19 +30,229 @@ pub struct UpdateTaskRequest {
     #[validate(length(min = 3, message = "Must have at least 3 characters"))]
     pub title: String,
     pub description: Option<String>,
-    pub status: String,
+    pub status: TaskStatus,
     pub due_date: Option<DateTimeWithTimeZone>,
     pub labels: Vec<String>,
+    /// See [`UpdateTaskStatusRequest::cascade_subtasks`].
+    #[serde(default)]
+    pub cascade_subtasks: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Validate)]
 pub struct UpdateTaskStatusRequest {
-    #[validate(length(min = 3, message = "Must have at least 3 characters"))]
-    pub status: String,
+    pub status: TaskStatus,
+    /// When marking a task as done, also mark its open subtasks as done instead of
+    /// rejecting the change.
+    #[serde(default)]
+    pub cascade_subtasks: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Validate)]
 pub struct UpdateTaskPriorityRequest {
-    #[validate(length(min = 3, message = "Must have at least 3 characters"))]
-    pub priority: String,
+    pub priority: TaskPriority,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct AddTaskDependencyRequest {
+    pub blocked_by_uuid: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct UpdateTaskParentRequest {
+    /// `null` turns the task back into a top level task.
+    pub parent_uuid: Option<String>,
+}
+
+/// Query string of `GET /api/tasks`. Unknown parameters are rejected rather than
+/// ignored so a typo doesn't silently return unfiltered tasks.
+#[derive(Debug, Default, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct TaskFilterQuery {
+    pub status: Option<TaskStatus>,
+    pub priority: Option<TaskPriority>,
+    /// Comma separated label titles, e.g. `labels=work,urgent`.
+    pub labels: Option<String>,
+    /// Whether tasks need `any` (the default) or `all` of `labels`.
+    #[serde(default)]
+    pub label_match: LabelMatch,
+    /// `true` for tasks without labels, `false` for tasks with at least one.
+    pub has_no_labels: Option<bool>,
+    #[validate(length(min = 1, message = "Must not be empty"))]
+    pub title_contains: Option<String>,
+    pub due_before: Option<DateTimeWithTimeZone>,
+    pub due_after: Option<DateTimeWithTimeZone>,
+    pub created_before: Option<DateTimeWithTimeZone>,
+    pub created_after: Option<DateTimeWithTimeZone>,
+    pub updated_before: Option<DateTimeWithTimeZone>,
+    pub updated_after: Option<DateTimeWithTimeZone>,
+    /// `true` for unfinished tasks past their due date, `false` for all others.
+    pub overdue: Option<bool>,
+    /// Comma separated sort keys, a leading `-` sorts descending, e.g.
+    /// `sort=-priority,due_date`. Defaults to `-date_created`.
+    #[validate(custom(function = "validate_sort"))]
+    pub sort: Option<String>,
+    /// `page`, `per_page` and `cursor` are read by `Pagination`, they are only listed
+    /// here so they aren't rejected as unknown.
+    #[serde(default, rename = "page")]
+    _page: Option<IgnoredAny>,
+    #[serde(default, rename = "per_page")]
+    _per_page: Option<IgnoredAny>,
+    #[serde(default, rename = "cursor")]
+    _cursor: Option<IgnoredAny>,
+}
+
+impl TaskFilterQuery {
+    /// The label titles in `labels`, without blanks and duplicates.
+    pub fn label_titles(&self) -> Vec<String> {
+        let mut titles: Vec<String> = Vec::new();
+
+        for title in self.labels.iter().flat_map(|labels| labels.split(',')) {
+            let title = title.trim();
+
+            if !title.is_empty() && !titles.iter().any(|existing| existing == title) {
+                titles.push(title.to_string());
+            }
+        }
+
+        titles
+    }
+
+    /// The parsed `sort` keys, newest first when none are given.
+    pub fn sort_keys(&self) -> Vec<TaskSort> {
+        self.sort
+            .as_deref()
+            .and_then(|sort| parse_sort(sort).ok())
+            .unwrap_or_else(|| {
+                vec![TaskSort {
+                    field: TaskSortField::DateCreated,
+                    descending: true,
+                }]
+            })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskSortField {
+    Title,
+    Status,
+    Priority,
+    DueDate,
+    DateCreated,
+    DateUpdated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskSort {
+    pub field: TaskSortField,
+    pub descending: bool,
+}
+
+fn parse_sort(sort: &str) -> Result<Vec<TaskSort>, String> {
+    let mut keys: Vec<TaskSort> = Vec::new();
+
+    for key in sort.split(',').map(str::trim) {
+        let (name, descending) = match key.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (key, false),
+        };
+
+        let field = match name {
+            "title" => TaskSortField::Title,
+            "status" => TaskSortField::Status,
+            "priority" => TaskSortField::Priority,
+            "due_date" => TaskSortField::DueDate,
+            "date_created" => TaskSortField::DateCreated,
+            "date_updated" => TaskSortField::DateUpdated,
+            _ => return Err(format!("Unknown sort key '{}'", key)),
+        };
+
+        if keys.iter().any(|existing| existing.field == field) {
+            return Err(format!("Duplicate sort key '{}'", name));
+        }
+
+        keys.push(TaskSort { field, descending });
+    }
+
+    Ok(keys)
+}
+
+fn validate_sort(sort: &str) -> Result<(), ValidationError> {
+    parse_sort(sort)
+        .map(|_| ())
+        .map_err(|message| ValidationError::new("invalid_sort").with_message(message.into()))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchTasksQuery {
+    #[validate(length(min = 1, max = 200, message = "Must be between 1 and 200 characters"))]
+    pub q: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RecurrenceRequest {
+    pub frequency: RecurrenceFrequency,
+    #[serde(default = "default_interval")]
+    #[validate(range(min = 1, max = 365, message = "Must be between 1 and 365"))]
+    pub interval: u32,
+    /// Days of the week the task recurs on, e.g. `["mon", "fri"]`. Only used for daily
+    /// and weekly series.
+    #[serde(default)]
+    pub by_weekday: Vec<Weekday>,
+    pub until: Option<DateTimeWithTimeZone>,
+    #[validate(range(min = 1, message = "Must be at least 1"))]
+    pub count: Option<u32>,
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+impl From<RecurrenceRequest> for RecurrenceRule {
+    fn from(value: RecurrenceRequest) -> Self {
+        let by_weekday = match value.frequency {
+            RecurrenceFrequency::Monthly => Vec::new(),
+            _ => value.by_weekday,
+        };
+
+        Self {
+            frequency: value.frequency,
+            interval: value.interval,
+            by_weekday,
+            until: value.until,
+            count: value.count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_keys_keep_their_order_and_direction() {
+        assert_eq!(
+            parse_sort("-priority,due_date"),
+            Ok(vec![
+                TaskSort {
+                    field: TaskSortField::Priority,
+                    descending: true,
+                },
+                TaskSort {
+                    field: TaskSortField::DueDate,
+                    descending: false,
+                },
+            ])
+        );
+        assert!(parse_sort("-priority,owner").is_err());
+        assert!(parse_sort("title,-title").is_err());
+        assert!(parse_sort("").is_err());
+    }
 }
@@ -1,5 +1,14 @@
-use crate::{models::_entities::user::ActiveModel, utils::hash};
-use sea_orm::Set;
+use std::sync::Arc;
+
+use crate::{
+    auth::{
+        password::{hash_blocking, PasswordHasher},
+        scope::Scope,
+    },
+    error::AppError,
+    models::_entities::{sea_orm_active_enums::UserRole, user::ActiveModel},
+};
+use sea_orm::{prelude::DateTimeWithTimeZone, Set};
 
 use serde::Deserialize;
 use validator::Validate;
@@ -15,15 +24,18 @@ pub struct CreateUserRequest {
     pub mobile_number: String,
 }
 
-impl From<CreateUserRequest> for ActiveModel {
-    fn from(value: CreateUserRequest) -> Self {
-        Self {
-            name: Set(value.name),
-            username: Set(value.username),
-            email: Set(value.email),
-            password: Set(hash(value.password.as_ref())),
+impl CreateUserRequest {
+    pub async fn into_active_model(
+        self,
+        password_hasher: &Arc<dyn PasswordHasher>,
+    ) -> Result<ActiveModel, AppError> {
+        Ok(ActiveModel {
+            password: Set(hash_blocking(password_hasher, &self.password).await?),
+            name: Set(self.name),
+            username: Set(self.username),
+            email: Set(self.email),
             ..Default::default()
-        }
+        })
     }
 }
 
@@ -35,6 +47,53 @@ pub struct UpdateUserRequest {
     pub email: String,
     #[validate(length(min = 8, message = "Must have at least 8 characters"))]
     pub password: Option<String>,
+    pub role: Option<UserRole>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateMeRequest {
+    #[validate(length(min = 3, message = "Must have at least 3 characters"))]
+    pub name: Option<String>,
+    #[validate(length(min = 3, message = "Must have at least 3 characters"))]
+    pub username: Option<String>,
+    #[validate(email(message = "Must be a valid email address"))]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateProfileRequest {
+    pub address: Option<String>,
+    pub mobile_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    #[validate(length(min = 8, message = "Must have at least 8 characters"))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email(message = "Must be a valid email address"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[validate(length(min = 8, message = "Must have at least 8 characters"))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogoutAllRequest {
+    pub before: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -44,3 +103,22 @@ pub struct UserLogin {
     #[validate(length(min = 8, message = "Must have at least 8 characters"))]
     pub password: String,
 }
+
+/// Filters of the admin list of login attempts.
+#[derive(Debug, Deserialize)]
+pub struct LoginAttemptQuery {
+    pub username: Option<String>,
+    pub user_id: Option<i32>,
+    pub succeeded: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateAccessTokenRequest {
+    #[validate(length(min = 1, max = 100, message = "Must have between 1 and 100 characters"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "Must grant at least one scope"))]
+    pub scopes: Vec<Scope>,
+    /// Tokens without an expiry stay valid until revoked.
+    #[validate(range(min = 1, max = 366, message = "Must be between 1 and 366"))]
+    pub expires_in_days: Option<i64>,
+}
@@ -0,0 +1,80 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+
+use super::{Email, Mailer};
+
+/// Writes every email to an `.eml` file below `dir`, which mail clients can open.
+#[derive(Debug, Clone)]
+pub struct FileMailer {
+    from: Mailbox,
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(from: Mailbox, dir: impl AsRef<Path>) -> Self {
+        Self {
+            from,
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> io::Result<()> {
+        let message = email.to_message(&self.from)?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        // sorts in the order the emails were sent
+        let name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            uuid::Uuid::new_v4()
+        );
+        let path = self.dir.join(name);
+
+        tokio::fs::write(&path, message.formatted()).await?;
+
+        tracing::info!(to = %email.to, path = %path.display(), "Email written to file");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_one_file_per_email() {
+        let dir = std::env::temp_dir().join(format!("mailer-{}", uuid::Uuid::new_v4()));
+        let mailer = FileMailer::new("no-reply@example.com".parse().unwrap(), &dir);
+        let email = Email {
+            to: "alice@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Hi Alice".to_string(),
+        };
+
+        mailer.send(&email).await.unwrap();
+        mailer.send(&email).await.unwrap();
+
+        let mut files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(files.len(), 2);
+        assert!(std::fs::read_to_string(&files[0])
+            .unwrap()
+            .contains("Subject: Hello"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use std::{fmt::Debug, io, sync::Arc};
+
+use async_trait::async_trait;
+use lettre::{message::Mailbox, Message};
+
+use crate::config::{MailConfig, MailTransport};
+
+pub mod file;
+pub mod log;
+pub mod outbox;
+pub mod smtp;
+
+/// A plain text email. Emails aren't sent directly but queued in the outbox, see
+/// [`outbox::enqueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails.
+#[async_trait]
+pub trait Mailer: Debug + Send + Sync {
+    async fn send(&self, email: &Email) -> io::Result<()>;
+}
+
+/// Picks the transport from `mail.transport`.
+pub fn from_config(config: &MailConfig) -> io::Result<Arc<dyn Mailer>> {
+    let from: Mailbox = config
+        .from
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    Ok(match config.transport {
+        MailTransport::Log => Arc::new(log::LogMailer::new(from)),
+        MailTransport::File => Arc::new(file::FileMailer::new(from, &config.file_dir)),
+        MailTransport::Smtp => Arc::new(smtp::SmtpMailer::new(from, &config.smtp)?),
+    })
+}
+
+impl Email {
+    /// The email as it goes over the wire.
+    pub fn to_message(&self, from: &Mailbox) -> io::Result<Message> {
+        let to: Mailbox = self.to.parse().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid recipient '{}': {}", self.to, err),
+            )
+        })?;
+
+        Message::builder()
+            .from(from.clone())
+            .to(to)
+            .subject(&self.subject)
+            .body(self.body.clone())
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emails_become_messages() {
+        let from: Mailbox = "Task App <no-reply@example.com>".parse().unwrap();
+        let email = Email {
+            to: "alice@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Hi Alice".to_string(),
+        };
+
+        let formatted = String::from_utf8(email.to_message(&from).unwrap().formatted()).unwrap();
+
+        assert!(formatted.contains("From: \"Task App\" <no-reply@example.com>"));
+        assert!(formatted.contains("To: alice@example.com"));
+        assert!(formatted.contains("Subject: Hello"));
+        assert!(formatted.ends_with("Hi Alice"));
+
+        let invalid = Email {
+            to: "not an address".to_string(),
+            ..email
+        };
+        assert!(invalid.to_message(&from).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use metrics::counter;
+use sea_orm::{
+    prelude::{DateTimeWithTimeZone, Expr},
+    ActiveModelTrait,
+    ActiveValue::NotSet,
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+use super::{Email, Mailer};
+use crate::{config::MailConfig, models::_entities::email_outbox};
+
+/// A claimed email isn't picked up by another worker for this long. When the
+/// instance delivering it stops midway, it's retried once the lease runs out.
+const LEASE: Duration = Duration::minutes(5);
+
+const BATCH_SIZE: u64 = 20;
+
+/// Queues an email, in the same transaction as the change it's about so neither
+/// happens without the other. It's sent by the [`Outbox`] worker.
+pub async fn enqueue<C>(db: &C, email: Email) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    email_outbox::ActiveModel {
+        id: NotSet,
+        recipient: Set(email.to),
+        subject: Set(email.subject),
+        body: Set(email.body),
+        attempts: Set(0),
+        last_error: Set(None),
+        next_attempt_at: Set(Utc::now().into()),
+        date_created: NotSet,
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Sends the emails queued in the `email_outbox` table, retrying failures with
+/// growing delays up to `mail.max_attempts` times.
+///
+/// Emails are sent at least once: one can go out twice when the process stops right
+/// after sending it. As they may hold secrets like reset links, sent emails are
+/// deleted, the ones given up on only keep their `last_error`, and none is kept
+/// longer than `expire_after`, when the links in it have stopped working.
+#[derive(Debug)]
+pub struct Outbox {
+    db: DatabaseConnection,
+    mailer: Arc<dyn Mailer>,
+    poll_interval: std::time::Duration,
+    max_attempts: i32,
+    expire_after: Duration,
+    wake: Notify,
+}
+
+impl Outbox {
+    pub fn new(
+        db: DatabaseConnection,
+        mailer: Arc<dyn Mailer>,
+        config: &MailConfig,
+        expire_after: Duration,
+    ) -> Self {
+        Self {
+            db,
+            mailer,
+            poll_interval: std::time::Duration::from_secs(config.poll_interval_seconds),
+            max_attempts: config.max_attempts,
+            expire_after,
+            wake: Notify::new(),
+        }
+    }
+
+    /// Delivers queued emails now rather than at the next poll, call it once the
+    /// transaction that queued them is committed.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let outbox = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = outbox.deliver_due().await {
+                    tracing::error!("Could not deliver queued emails: {}", err);
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(outbox.poll_interval) => {},
+                    _ = outbox.wake.notified() => {},
+                }
+            }
+        })
+    }
+
+    async fn deliver_due(&self) -> Result<(), DbErr> {
+        self.delete_expired().await?;
+
+        loop {
+            let due = email_outbox::Entity::find()
+                .filter(email_outbox::Column::NextAttemptAt.lte(Utc::now()))
+                .filter(email_outbox::Column::Attempts.lt(self.max_attempts))
+                .order_by_asc(email_outbox::Column::Id)
+                .limit(BATCH_SIZE)
+                .all(&self.db)
+                .await?;
+
+            let drained = (due.len() as u64) < BATCH_SIZE;
+
+            for email in due {
+                self.deliver(email).await?;
+            }
+
+            if drained {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn delete_expired(&self) -> Result<(), DbErr> {
+        let cutoff: DateTimeWithTimeZone = (Utc::now() - self.expire_after).into();
+
+        let deleted = email_outbox::Entity::delete_many()
+            .filter(email_outbox::Column::DateCreated.lt(cutoff))
+            .exec(&self.db)
+            .await?;
+
+        if deleted.rows_affected > 0 {
+            tracing::info!(
+                count = deleted.rows_affected,
+                "Deleted expired emails from the outbox"
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn deliver(&self, email: email_outbox::Model) -> Result<(), DbErr> {
+        let now = Utc::now();
+        let lease_until: DateTimeWithTimeZone = (now + LEASE).into();
+
+        // only one worker gets to send it, the others find it no longer due
+        let claimed = email_outbox::Entity::update_many()
+            .col_expr(
+                email_outbox::Column::NextAttemptAt,
+                Expr::value(lease_until),
+            )
+            .filter(email_outbox::Column::Id.eq(email.id))
+            .filter(email_outbox::Column::NextAttemptAt.lte(now))
+            .exec(&self.db)
+            .await?;
+
+        if claimed.rows_affected == 0 {
+            return Ok(());
+        }
+
+        let message = Email {
+            to: email.recipient.clone(),
+            subject: email.subject.clone(),
+            body: email.body.clone(),
+        };
+
+        match self.mailer.send(&message).await {
+            Ok(()) => {
+                counter!("emails_total", "result" => "sent").increment(1);
+
+                email_outbox::Entity::delete_by_id(email.id)
+                    .exec(&self.db)
+                    .await?;
+            }
+            Err(err) => {
+                counter!("emails_total", "result" => "failed").increment(1);
+
+                let attempts = email.attempts + 1;
+                let given_up = attempts >= self.max_attempts;
+
+                if given_up {
+                    tracing::error!(
+                        email_id = email.id,
+                        attempts,
+                        "Giving up on sending an email: {}",
+                        err
+                    );
+                } else {
+                    tracing::warn!(
+                        email_id = email.id,
+                        attempts,
+                        "Could not send an email: {}",
+                        err
+                    );
+                }
+
+                let next_attempt_at: DateTimeWithTimeZone = (now + retry_delay(attempts)).into();
+
+                let mut email: email_outbox::ActiveModel = email.into();
+                email.attempts = Set(attempts);
+                email.last_error = Set(Some(err.to_string()));
+                email.next_attempt_at = Set(next_attempt_at);
+                if given_up {
+                    email.body = Set(String::new());
+                }
+                email.update(&self.db).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 30 seconds after the first failure, doubling with every further one up to an hour.
+fn retry_delay(attempts: i32) -> Duration {
+    let doublings = (attempts - 1).clamp(0, 7) as u32;
+
+    (Duration::seconds(30) * (1 << doublings)).min(Duration::hours(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_up_to_an_hour() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::minutes(1));
+        assert_eq!(retry_delay(4), Duration::minutes(4));
+        assert_eq!(retry_delay(8), Duration::hours(1));
+        assert_eq!(retry_delay(30), Duration::hours(1));
+    }
+}
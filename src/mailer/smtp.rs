@@ -0,0 +1,57 @@
+use std::{io, time::Duration};
+
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Tokio1Executor,
+};
+
+use super::{Email, Mailer};
+use crate::config::{SmtpConfig, SmtpTls};
+
+/// Sends emails through an SMTP server. With `tls = "none"` it can also talk to a
+/// local SMTP sink such as MailHog or Mailpit during development.
+#[derive(Debug, Clone)]
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(from: Mailbox, config: &SmtpConfig) -> io::Result<Self> {
+        let builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(io::Error::other)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(io::Error::other)?,
+        };
+
+        let mut builder = builder
+            .port(config.port)
+            .timeout(Some(Duration::from_secs(config.timeout_seconds)));
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            from,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> io::Result<()> {
+        let message = email.to_message(&self.from)?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(io::Error::other)?;
+
+        Ok(())
+    }
+}
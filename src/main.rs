@@ -1,65 +1,151 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use axum::{http::StatusCode, Router};
-use sea_orm::{Database, DatabaseConnection};
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    Router,
+};
+use clap::Parser;
+use metrics_exporter_prometheus::PrometheusHandle;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
 use tokio::{net::TcpListener, signal};
-use tower_http::trace::TraceLayer;
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::{
+    password::{Argon2Hasher, PasswordHasher},
+    revocation::RevokedTokens,
+};
+use crate::config::{AppConfig, Cli, CorsConfig, LogFormat};
+use crate::mailer::outbox::Outbox;
+use crate::rate_limit::{memory::InMemoryStore, RateLimitStore};
+use crate::storage::Storage;
+use crate::workflow::TaskWorkflow;
 
 mod api_response;
 mod auth;
+mod client_info;
+mod config;
 mod controller;
 mod error;
 mod form;
+mod mailer;
 mod middlewares;
 mod models;
+mod pagination;
+mod rate_limit;
+mod recurrence;
 mod serializer;
+mod storage;
+mod telemetry;
 mod utils;
+mod workflow;
 
 #[derive(Clone, Debug)]
 struct AppState {
+    config: Arc<AppConfig>,
     db: DatabaseConnection,
+    metrics: PrometheusHandle,
+    outbox: Arc<Outbox>,
+    password_hasher: Arc<dyn PasswordHasher>,
+    rate_limiter: Arc<dyn RateLimitStore>,
+    revoked_tokens: Arc<RevokedTokens>,
+    storage: Arc<dyn Storage>,
+    task_workflow: TaskWorkflow,
 }
 
 #[tokio::main]
 async fn main() {
-    dotenvy::dotenv().expect("Unable to access .env file");
+    // the environment may also be set without a .env file
+    dotenvy::dotenv().ok();
+
+    let config = AppConfig::load(Cli::parse()).unwrap_or_else(|err| {
+        eprint!("{}", err);
+        std::process::exit(1);
+    });
 
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
-        .pretty()
-        .with_ansi(true)
-        .init();
+    let subscriber =
+        tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&config.logging.level));
 
-    let server_address = std::env::var("SERVER_ADDRESS").expect("Server Address not found");
+    match config.logging.format {
+        LogFormat::Pretty => subscriber.pretty().with_ansi(true).init(),
+        LogFormat::Compact => subscriber.compact().with_ansi(true).init(),
+        LogFormat::Json => subscriber.json().with_ansi(false).init(),
+    }
 
-    tracing::info!("Listening on {}", server_address);
+    tracing::info!("Listening on {}", config.server.address);
 
-    let listener = TcpListener::bind(server_address.clone())
+    let listener = TcpListener::bind(&config.server.address)
         .await
         .expect("Could not create TCP Listener");
 
-    let app = create_app().await;
+    let app = create_app(config).await;
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    // client addresses are needed for rate limiting
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
 }
 
-async fn create_app() -> Router {
-    let database_url = std::env::var("DATABASE_URL").expect("Database url not found");
+async fn create_app(config: AppConfig) -> Router {
+    let mut connect_options = ConnectOptions::new(&config.database.url);
+    connect_options
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .connect_timeout(Duration::from_secs(config.database.connect_timeout_seconds))
+        .idle_timeout(Duration::from_secs(config.database.idle_timeout_seconds));
 
-    let db = Database::connect(&database_url)
+    let mut db = Database::connect(connect_options)
         .await
         .expect("Cannot connect to a database");
+    telemetry::instrument_database(&mut db);
+
+    let cors_layer = cors_layer(&config.cors);
 
-    let app_state = Arc::new(AppState { db });
+    let mailer = mailer::from_config(&config.mail).expect("Cannot set up the mail transport");
+    let outbox = Arc::new(Outbox::new(
+        db.clone(),
+        mailer,
+        &config.mail,
+        chrono::Duration::minutes(config.auth.password_reset_token_minutes),
+    ));
+    outbox.spawn();
+
+    let storage = storage::from_config(&config.storage);
+    let task_workflow = TaskWorkflow::from(&config.workflow);
+
+    let app_state = Arc::new(AppState {
+        config: Arc::new(config),
+        db,
+        metrics: telemetry::recorder(),
+        outbox,
+        password_hasher: Arc::new(Argon2Hasher::default()),
+        rate_limiter: Arc::new(InMemoryStore::default()),
+        revoked_tokens: Arc::new(RevokedTokens::default()),
+        storage,
+        task_workflow,
+    });
 
     Router::new()
         .nest(
             "/api/tasks",
             controller::task_controller::get_routes().await,
         )
+        .nest(
+            "/api/tasks/{task_uuid}/comments",
+            controller::comment_controller::get_routes().await,
+        )
+        .nest(
+            "/api/tasks/{task_uuid}/attachments",
+            controller::attachment_controller::get_routes(&app_state.config.storage).await,
+        )
         .nest(
             "/api/users",
             controller::user_controller::get_routes().await,
@@ -68,26 +154,119 @@ async fn create_app() -> Router {
             "/api/labels",
             controller::label_controller::get_routes().await,
         )
-        // .nest("/api", controller::auth_controller::get_routes().await)
+        .nest("/api/me", controller::me_controller::get_routes().await)
+        .nest(
+            "/api/me/tokens",
+            controller::token_controller::get_routes().await,
+        )
         .nest(
             "/api/auth",
             controller::auth_controller::get_logout_route().await,
         )
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middlewares::rate_limit_guard::per_user,
+        ))
         .route_layer(axum::middleware::from_fn_with_state(
             app_state.clone(),
             middlewares::auth_guard::auth_guard,
         ))
-        .nest(
-            "/api/auth",
-            controller::auth_controller::get_login_route().await,
+        // before the token is checked, so requests with bad tokens are throttled too
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middlewares::rate_limit_guard::per_ip,
+        ))
+        .merge(controller::health_controller::get_routes().await)
+        .merge(controller::metrics_controller::get_routes().await)
+        .merge(
+            Router::new()
+                .nest(
+                    "/api/auth",
+                    controller::auth_controller::get_login_route().await,
+                )
+                .nest(
+                    "/api/auth",
+                    controller::auth_controller::get_register_route().await,
+                )
+                .nest(
+                    "/api/auth",
+                    controller::auth_controller::get_password_reset_route().await,
+                )
+                .route_layer(axum::middleware::from_fn_with_state(
+                    app_state.clone(),
+                    middlewares::rate_limit_guard::login,
+                )),
         )
-        .nest(
-            "/api/auth",
-            controller::auth_controller::get_register_route().await,
+        .merge(
+            Router::new()
+                .nest(
+                    "/api/auth",
+                    controller::auth_controller::get_refresh_route().await,
+                )
+                .route_layer(axum::middleware::from_fn_with_state(
+                    app_state.clone(),
+                    middlewares::rate_limit_guard::per_ip,
+                )),
         )
         .with_state(app_state)
         .fallback(fallback_handler)
-        .layer(TraceLayer::new_for_http())
+        .layer(axum::middleware::from_fn(
+            middlewares::request_metrics::request_metrics,
+        ))
+        .layer(axum::middleware::from_fn(
+            middlewares::problem_json::problem_json,
+        ))
+        .layer(cors_layer)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(tracing::Level::INFO)),
+        )
+        .layer(axum::middleware::from_fn(
+            middlewares::request_id::request_id,
+        ))
+}
+
+/// Span every log line of a request is recorded in, `user_id` is filled in by
+/// `auth_guard` once the user is known.
+fn request_span(request: &Request) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get(middlewares::request_id::X_REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id,
+        user_id = tracing::field::Empty,
+    )
+}
+
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    if config.allowed_origins.is_empty() {
+        return CorsLayer::new();
+    }
+
+    let allow_origin = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        // validated when the config is loaded
+        AllowOrigin::list(
+            config
+                .allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .max_age(Duration::from_secs(config.max_age_seconds))
 }
 
 async fn fallback_handler() -> StatusCode {
@@ -121,20 +300,34 @@ async fn shutdown_signal() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::{body::Body, http::Request};
-    use dotenvy::dotenv;
-    use tower::{Service, ServiceExt};
+    use crate::auth::jwt::{create_user_token, TokenClaims, TokenType};
+    use crate::models::testing;
+    use axum::{
+        body::Body,
+        http::{header, Request},
+    };
+    use tower::ServiceExt;
 
     #[tokio::test]
     async fn hello_world() {
-        dotenv().ok();
+        let mut config = AppConfig::default();
+        config.database.url = "sqlite:file:hello_world?mode=memory&cache=shared".to_string();
+        config.auth.jwt_secret = "test-secret".to_string();
+
+        // also keeps the shared in-memory database alive for the app's connections
+        let db = testing::database(&config.database.url).await;
+        let user = testing::user(&db, "ada").await;
+
+        let claims = TokenClaims::new(&user.id.to_string(), TokenType::Access, "test", 5);
+        let token = create_user_token(&claims, &config.auth.jwt_secret).await;
 
-        let app = create_app().await;
+        let app = create_app(config).await;
 
         let response = app
             .oneshot(
                 Request::builder()
                     .uri("/api/tasks")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", token))
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -1,13 +1,25 @@
 use std::sync::Arc;
 
-use crate::{error::AppError, utils::verify_token, AppState};
+use crate::{
+    auth::{
+        personal_access_token,
+        scope::{required_scope, Scope},
+    },
+    error::AppError,
+    utils::verify_token,
+    AppState,
+};
 use axum::{
-    extract::{Request, State},
+    extract::{MatchedPath, Request, State},
     http::header,
     middleware::Next,
     response::Response,
 };
+use metrics::counter;
+use sea_orm::DbErr;
 
+/// Authenticates with either an access token from login or a personal access token.
+/// Personal access tokens are also checked against the scope the route requires.
 pub async fn auth_guard(
     State(app_state): State<Arc<AppState>>,
     mut request: Request,
@@ -18,15 +30,66 @@ pub async fn auth_guard(
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok())
         .and_then(|header| header.strip_prefix("Bearer "))
-        .ok_or(AppError::Unauthorized(
-            "Authentication credentials were not provided.".into(),
-        ))?;
+        .ok_or_else(|| {
+            counter!("auth_failures_total", "reason" => "missing_credentials").increment(1);
+
+            AppError::Unauthorized("Authentication credentials were not provided.".into())
+        })?;
+
+    let count_invalid = |err: &AppError| {
+        // a database outage isn't an authentication failure
+        if matches!(
+            err,
+            AppError::Unauthorized(_) | AppError::SeaOrm(DbErr::RecordNotFound(_))
+        ) {
+            counter!("auth_failures_total", "reason" => "invalid_credentials").increment(1);
+        }
+    };
+
+    if token.starts_with(personal_access_token::TOKEN_PREFIX) {
+        let (user, access_token) = personal_access_token::authenticate(&app_state.db, token)
+            .await
+            .inspect_err(count_invalid)?;
+
+        check_scope(&request, &access_token.scopes)?;
+
+        tracing::Span::current().record("user_id", user.id);
 
-    let user = verify_token(app_state, token).await?;
+        request.extensions_mut().insert(user);
+        request.extensions_mut().insert(access_token);
+    } else {
+        let (user, token_claims) = verify_token(app_state, token)
+            .await
+            .inspect_err(count_invalid)?;
 
-    request.extensions_mut().insert(user);
+        tracing::Span::current().record("user_id", user.id);
+
+        request.extensions_mut().insert(user);
+        request.extensions_mut().insert(token_claims);
+    }
 
     let response = next.run(request).await;
 
     Ok(response)
 }
+
+fn check_scope(request: &Request, scopes: &str) -> Result<(), AppError> {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(request.uri().path(), MatchedPath::as_str);
+
+    let granted = Scope::split(scopes);
+
+    match required_scope(request.method(), path) {
+        Some(required) if granted.iter().any(|scope| scope.grants(required)) => Ok(()),
+        required => {
+            counter!("auth_failures_total", "reason" => "insufficient_scope").increment(1);
+
+            Err(AppError::Forbidden(match required {
+                Some(required) => format!("This token lacks the {} scope.", required),
+                None => "Personal access tokens can't be used for this action.".to_string(),
+            }))
+        }
+    }
+}
@@ -1 +1,6 @@
 pub mod auth_guard;
+pub mod problem_json;
+pub mod rate_limit_guard;
+pub mod request_id;
+pub mod request_metrics;
+pub mod role_guard;
@@ -0,0 +1,41 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::error::ErrorDetails;
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Renders errors as RFC 7807 `application/problem+json` for clients that list it in
+/// `Accept`, everyone else keeps getting the usual JSON error body.
+pub async fn problem_json(request: Request, next: Next) -> Response {
+    let wants_problem = request
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .any(|accept| accept.contains(PROBLEM_JSON));
+
+    let mut response = next.run(request).await;
+
+    if !wants_problem {
+        return response;
+    }
+
+    let Some(details) = response.extensions_mut().remove::<ErrorDetails>() else {
+        return response;
+    };
+
+    // headers like `Retry-After` still apply, only the body changes
+    let (mut parts, _) = response.into_parts();
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    Response::from_parts(parts, Body::from(details.problem().to_string()))
+}
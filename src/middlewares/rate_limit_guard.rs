@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::counter;
+
+use crate::{
+    client_info::ClientInfo,
+    error::AppError,
+    models::_entities::user,
+    rate_limit::{Decision, Quota},
+    AppState,
+};
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+/// Limits authenticated routes per user, so has to run after `auth_guard`.
+pub async fn per_user(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = match request.extensions().get::<user::Model>() {
+        Some(user) => format!("user:{}", user.id),
+        None => ip_key(&app_state, &request),
+    };
+    let quota = app_state.config.rate_limit.authenticated;
+
+    limit(&app_state, "authenticated", quota, key, request, next).await
+}
+
+/// Limits requests per client IP, on anonymous routes and ahead of `auth_guard`.
+pub async fn per_ip(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = ip_key(&app_state, &request);
+    let quota = app_state.config.rate_limit.anonymous;
+
+    limit(&app_state, "anonymous", quota, key, request, next).await
+}
+
+/// Limits login and registration per client IP, with a budget of their own that's
+/// small enough to make guessing passwords impractical.
+pub async fn login(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = format!("login:{}", ip_key(&app_state, &request));
+    let quota = app_state.config.rate_limit.login;
+
+    limit(&app_state, "login", quota, key, request, next).await
+}
+
+async fn limit(
+    app_state: &AppState,
+    budget: &'static str,
+    quota: Quota,
+    key: String,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !app_state.config.rate_limit.enabled {
+        return next.run(request).await;
+    }
+
+    // an unavailable store shouldn't take the API down with it
+    let decision = match app_state.rate_limiter.acquire(&key, quota).await {
+        Ok(decision) => decision,
+        Err(err) => {
+            tracing::warn!("Rate limiter unavailable, request let through: {}", err);
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        counter!("rate_limited_total", "budget" => budget).increment(1);
+
+        AppError::TooManyRequests(whole_seconds(decision.retry_after.as_secs_f64())).into_response()
+    };
+
+    insert_headers(response.headers_mut(), &decision, quota);
+
+    response
+}
+
+/// `RateLimit-*` headers as in the IETF draft, on every limited response.
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision, quota: Quota) {
+    headers.insert(RATELIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATELIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(
+        RATELIMIT_RESET,
+        HeaderValue::from(whole_seconds(decision.reset_after.as_secs_f64())),
+    );
+
+    if let Ok(policy) =
+        HeaderValue::from_str(&format!("{};w={}", quota.requests, quota.period_seconds))
+    {
+        headers.insert(RATELIMIT_POLICY, policy);
+    }
+}
+
+/// Rounded up, so clients never come back too early.
+fn whole_seconds(seconds: f64) -> u64 {
+    seconds.ceil() as u64
+}
+
+fn ip_key(app_state: &AppState, request: &Request) -> String {
+    let client = ClientInfo::from_parts(
+        request.headers(),
+        request.extensions(),
+        app_state.config.rate_limit.trust_forwarded_for,
+    );
+
+    match client.ip {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    }
+}
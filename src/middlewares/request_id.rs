@@ -0,0 +1,61 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, `None` outside of one.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Gives every request an id, the caller's `X-Request-Id` when it's usable and a new
+/// one otherwise. It's put back on the request for the trace span, echoed on the
+/// response and used as the `correlation_id` of errors.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let header = HeaderValue::from_str(&id).expect("Request id is a valid header value");
+    request.headers_mut().insert(X_REQUEST_ID, header.clone());
+
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    response.headers_mut().insert(X_REQUEST_ID, header);
+
+    response
+}
+
+/// Ids end up in every log line, so only short ones of plain characters are kept.
+fn is_valid(id: &str) -> bool {
+    (1..=128).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_ids_are_accepted() {
+        assert!(is_valid("5f0c6a1e-9b7d-4a39-8f0e-2d8c1b7a6e55"));
+        assert!(is_valid("frontend:1234"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("id with spaces"));
+        assert!(!is_valid("line\nbreak"));
+        assert!(!is_valid(&"a".repeat(129)));
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, gauge, histogram, Gauge};
+
+/// Counts requests and their latency by method, matched route and status. Routes are
+/// labelled by their pattern, e.g. `/api/tasks/{task_uuid}`, to keep the series few.
+pub async fn request_metrics(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+
+    let _in_flight = InFlight::start();
+    let started_at = Instant::now();
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels).record(started_at.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Decrements the in-flight gauge when dropped, also when the client goes away before
+/// the response is ready.
+struct InFlight(Gauge);
+
+impl InFlight {
+    fn start() -> Self {
+        let gauge = gauge!("http_requests_in_flight");
+        gauge.increment(1);
+        Self(gauge)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.decrement(1);
+    }
+}
@@ -0,0 +1,27 @@
+use axum::{extract::Request, middleware::Next, response::Response, Extension};
+
+use crate::{
+    error::AppError,
+    models::_entities::{sea_orm_active_enums::UserRole, user},
+};
+
+/// Only lets admins through. Has to run after `auth_guard`, which provides the user.
+pub async fn admin_guard(
+    Extension(user): Extension<user::Model>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_role(&user, UserRole::Admin)?;
+
+    Ok(next.run(request).await)
+}
+
+pub fn require_role(user: &user::Model, role: UserRole) -> Result<(), AppError> {
+    if user.role != role {
+        return Err(AppError::Forbidden(
+            "You do not have permission to perform this action.".into(),
+        ));
+    }
+
+    Ok(())
+}
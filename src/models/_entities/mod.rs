@@ -2,8 +2,19 @@
 
 pub mod prelude;
 
+pub mod attachment;
+pub mod email_outbox;
 pub mod label;
+pub mod login_attempt;
+pub mod password_reset_token;
+pub mod personal_access_token;
+pub mod refresh_token;
+pub mod revoked_token;
+pub mod sea_orm_active_enums;
 pub mod task;
+pub mod task_comment;
+pub mod task_dependency;
 pub mod task_label;
+pub mod task_series;
 pub mod user;
 pub mod user_profile;
@@ -1,7 +1,17 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+pub use super::attachment::Entity as Attachment;
+pub use super::email_outbox::Entity as EmailOutbox;
 pub use super::label::Entity as Label;
+pub use super::login_attempt::Entity as LoginAttempt;
+pub use super::password_reset_token::Entity as PasswordResetToken;
+pub use super::personal_access_token::Entity as PersonalAccessToken;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::revoked_token::Entity as RevokedToken;
 pub use super::task::Entity as Task;
+pub use super::task_comment::Entity as TaskComment;
+pub use super::task_dependency::Entity as TaskDependency;
 pub use super::task_label::Entity as TaskLabel;
+pub use super::task_series::Entity as TaskSeries;
 pub use super::user::Entity as User;
 pub use super::user_profile::Entity as UserProfile;
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+use super::sea_orm_active_enums::{TaskPriority, TaskStatus};
 use sea_orm::entity::prelude::*;
 use serde::Serialize;
 
@@ -11,20 +12,43 @@ pub struct Model {
     pub title: String,
     #[sea_orm(column_type = "Text")]
     pub description: String,
-    pub status: String,
-    pub priority: String,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
     #[sea_orm(unique)]
     pub uuid: String,
     pub due_date: Option<DateTimeWithTimeZone>,
     pub date_created: DateTimeWithTimeZone,
     pub date_updated: Option<DateTimeWithTimeZone>,
     pub user_id: i32,
+    pub parent_id: Option<i32>,
+    pub series_id: Option<i32>,
+    pub comment_count: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::attachment::Entity")]
+    Attachment,
+    #[sea_orm(
+        belongs_to = "Entity",
+        from = "Column::ParentId",
+        to = "Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    SelfRef,
+    #[sea_orm(has_many = "super::task_comment::Entity")]
+    TaskComment,
     #[sea_orm(has_many = "super::task_label::Entity")]
     TaskLabel,
+    #[sea_orm(
+        belongs_to = "super::task_series::Entity",
+        from = "Column::SeriesId",
+        to = "super::task_series::Column::Id",
+        on_update = "Cascade",
+        on_delete = "SetNull"
+    )]
+    TaskSeries,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -35,12 +59,30 @@ pub enum Relation {
     User,
 }
 
+impl Related<super::attachment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attachment.def()
+    }
+}
+
 impl Related<super::task_label::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::TaskLabel.def()
     }
 }
 
+impl Related<super::task_comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TaskComment.def()
+    }
+}
+
+impl Related<super::task_series::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TaskSeries.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
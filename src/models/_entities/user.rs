@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+use super::sea_orm_active_enums::UserRole;
 use sea_orm::entity::prelude::*;
 use serde::Serialize;
 
@@ -16,30 +17,99 @@ pub struct Model {
     pub password: String,
     pub date_created: DateTimeWithTimeZone,
     pub date_updated: Option<DateTimeWithTimeZone>,
+    pub token_valid_after: Option<DateTimeWithTimeZone>,
+    pub role: UserRole,
+    pub failed_login_count: i32,
+    pub last_failed_login_at: Option<DateTimeWithTimeZone>,
+    pub locked_until: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::attachment::Entity")]
+    Attachment,
     #[sea_orm(has_many = "super::label::Entity")]
     Label,
+    #[sea_orm(has_many = "super::login_attempt::Entity")]
+    LoginAttempt,
+    #[sea_orm(has_many = "super::password_reset_token::Entity")]
+    PasswordResetToken,
+    #[sea_orm(has_many = "super::personal_access_token::Entity")]
+    PersonalAccessToken,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
+    #[sea_orm(has_many = "super::revoked_token::Entity")]
+    RevokedToken,
     #[sea_orm(has_many = "super::task::Entity")]
     Task,
+    #[sea_orm(has_many = "super::task_comment::Entity")]
+    TaskComment,
+    #[sea_orm(has_many = "super::task_series::Entity")]
+    TaskSeries,
     #[sea_orm(has_many = "super::user_profile::Entity")]
     UserProfile,
 }
 
+impl Related<super::attachment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attachment.def()
+    }
+}
+
 impl Related<super::label::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Label.def()
     }
 }
 
+impl Related<super::login_attempt::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoginAttempt.def()
+    }
+}
+
+impl Related<super::password_reset_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResetToken.def()
+    }
+}
+
+impl Related<super::personal_access_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PersonalAccessToken.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
+impl Related<super::revoked_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RevokedToken.def()
+    }
+}
+
 impl Related<super::task::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Task.def()
     }
 }
 
+impl Related<super::task_comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TaskComment.def()
+    }
+}
+
+impl Related<super::task_series::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TaskSeries.def()
+    }
+}
+
 impl Related<super::user_profile::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserProfile.def()
@@ -0,0 +1,31 @@
+use sea_orm::{
+    prelude::Expr,
+    sea_query::{Alias, ExprTrait, Func},
+    ActiveModelBehavior, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    QuerySelect,
+};
+
+use super::_entities::attachment::{ActiveModel, Column, Entity};
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Bytes stored for the user across all their attachments.
+pub async fn total_size_for_user<C>(db: &C, user_id: i32) -> Result<i64, DbErr>
+where
+    C: ConnectionTrait,
+{
+    // SUM over a bigint is a numeric on Postgres, cast it back
+    let total: Option<i64> = Entity::find()
+        .select_only()
+        .column_as(
+            Func::coalesce([Expr::col(Column::Size).sum(), Expr::val(0).into()])
+                .cast_as(Alias::new("bigint")),
+            "total",
+        )
+        .filter(Column::UserId.eq(user_id))
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    Ok(total.unwrap_or_default())
+}
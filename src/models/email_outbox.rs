@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::email_outbox::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
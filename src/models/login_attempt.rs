@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::login_attempt::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
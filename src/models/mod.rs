@@ -1,6 +1,19 @@
+#[allow(unused_imports)]
 pub mod _entities;
+pub mod attachment;
+pub mod email_outbox;
 pub mod label;
+pub mod login_attempt;
+pub mod password_reset_token;
+pub mod personal_access_token;
+pub mod refresh_token;
+pub mod revoked_token;
 pub mod task;
+pub mod task_comment;
+pub mod task_dependency;
 pub mod task_label;
+pub mod task_series;
+#[cfg(test)]
+pub mod testing;
 pub mod user;
 pub mod user_profile;
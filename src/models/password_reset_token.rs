@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::password_reset_token::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
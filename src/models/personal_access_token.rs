@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::personal_access_token::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::refresh_token::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
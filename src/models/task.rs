@@ -1,10 +1,18 @@
-use sea_orm::{ActiveModelBehavior, Related, RelationDef, RelationTrait as _};
+use std::collections::HashSet;
+
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ConnectionTrait,
+    DatabaseBackend, DbErr, EntityTrait, FromQueryResult, PaginatorTrait, QueryFilter, QuerySelect,
+    Related, RelationDef, RelationTrait as _, Set, Statement, Value,
+};
 
 use super::_entities::{
     label,
-    task::{ActiveModel, Entity},
-    task_label,
+    sea_orm_active_enums::TaskStatus,
+    task::{ActiveModel, Column, Entity, Model},
+    task_dependency, task_label, task_series,
 };
+use crate::recurrence::RecurrenceRule;
 
 impl Related<label::Entity> for Entity {
     fn to() -> RelationDef {
@@ -16,3 +24,446 @@ impl Related<label::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Subtasks at any depth below this task, level by level.
+    pub async fn find_descendants<C>(&self, db: &C) -> Result<Vec<Model>, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let mut descendants = Vec::new();
+        let mut seen = HashSet::from([self.id]);
+        let mut parent_ids = vec![self.id];
+
+        while !parent_ids.is_empty() {
+            let children: Vec<Model> = Entity::find()
+                .filter(Column::ParentId.is_in(parent_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .filter(|task| seen.insert(task.id))
+                .collect();
+
+            parent_ids = children.iter().map(|task| task.id).collect();
+            descendants.extend(children);
+        }
+
+        Ok(descendants)
+    }
+
+    /// Whether making `parent` the parent of this task would create a cycle, i.e. this
+    /// task is `parent` itself or one of its ancestors.
+    pub async fn would_create_cycle<C>(&self, db: &C, parent: &Model) -> Result<bool, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let mut seen = HashSet::new();
+        let mut current = Some(parent.clone());
+
+        while let Some(task) = current {
+            // the second check guards against cycles that are already in the data
+            if task.id == self.id || !seen.insert(task.id) {
+                return Ok(true);
+            }
+
+            current = match task.parent_id {
+                Some(parent_id) => Entity::find_by_id(parent_id).one(db).await?,
+                None => None,
+            };
+        }
+
+        Ok(false)
+    }
+
+    /// Tasks this task is waiting on.
+    pub async fn find_blockers<C>(&self, db: &C) -> Result<Vec<Model>, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let blocker_ids: Vec<i32> = task_dependency::Entity::find()
+            .select_only()
+            .column(task_dependency::Column::BlockedById)
+            .filter(task_dependency::Column::TaskId.eq(self.id))
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        Entity::find()
+            .filter(Column::Id.is_in(blocker_ids))
+            .all(db)
+            .await
+    }
+
+    /// Tasks waiting on this task.
+    pub async fn find_blocked_tasks<C>(&self, db: &C) -> Result<Vec<Model>, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let blocked_ids: Vec<i32> = task_dependency::Entity::find()
+            .select_only()
+            .column(task_dependency::Column::TaskId)
+            .filter(task_dependency::Column::BlockedById.eq(self.id))
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        Entity::find()
+            .filter(Column::Id.is_in(blocked_ids))
+            .all(db)
+            .await
+    }
+
+    /// Whether this task waits on the task `other_id`, directly or through other tasks.
+    pub async fn depends_on<C>(&self, db: &C, other_id: i32) -> Result<bool, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let mut seen = HashSet::from([self.id]);
+        let mut task_ids = vec![self.id];
+
+        while !task_ids.is_empty() {
+            let blocker_ids: Vec<i32> = task_dependency::Entity::find()
+                .select_only()
+                .column(task_dependency::Column::BlockedById)
+                .filter(task_dependency::Column::TaskId.is_in(task_ids))
+                .into_tuple()
+                .all(db)
+                .await?;
+
+            if blocker_ids.contains(&other_id) {
+                return Ok(true);
+            }
+
+            task_ids = blocker_ids
+                .into_iter()
+                .filter(|blocker_id| seen.insert(*blocker_id))
+                .collect();
+        }
+
+        Ok(false)
+    }
+
+    /// Creates the next occurrence of a recurring task, copying its labels, once the
+    /// task is done. Returns `None` when the task isn't part of an active series, the
+    /// series is over, or the next occurrence already exists.
+    pub async fn create_next_occurrence<C>(&self, db: &C) -> Result<Option<Model>, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let Some(series_id) = self.series_id else {
+            return Ok(None);
+        };
+
+        let Some(series) = task_series::Entity::find_by_id(series_id).one(db).await? else {
+            return Ok(None);
+        };
+
+        if !series.active {
+            return Ok(None);
+        }
+
+        // reopening and completing an occurrence again must not spawn a second one
+        let later_occurrences = Entity::find()
+            .filter(Column::SeriesId.eq(series_id))
+            .filter(Column::Id.gt(self.id))
+            .count(db)
+            .await?;
+
+        if later_occurrences > 0 {
+            return Ok(None);
+        }
+
+        let occurrence_count = series.occurrence_count;
+        let after = self.due_date.unwrap_or_else(|| chrono::Utc::now().into());
+        let next_due_date = RecurrenceRule::from(&series).next_occurrence(
+            series.starts_at,
+            after,
+            occurrence_count.max(0) as u32,
+        );
+
+        let mut series: task_series::ActiveModel = series.into();
+        series.date_updated = Set(Some(chrono::Utc::now().into()));
+
+        let Some(next_due_date) = next_due_date else {
+            series.active = Set(false);
+            series.update(db).await?;
+
+            return Ok(None);
+        };
+
+        let next_task = ActiveModel {
+            id: NotSet,
+            title: Set(self.title.clone()),
+            description: Set(self.description.clone()),
+            status: Set(TaskStatus::Pending),
+            priority: Set(self.priority),
+            uuid: Set(uuid::Uuid::new_v4().to_string()),
+            due_date: Set(Some(next_due_date)),
+            date_created: NotSet,
+            date_updated: NotSet,
+            user_id: Set(self.user_id),
+            parent_id: Set(self.parent_id),
+            series_id: Set(Some(series_id)),
+            comment_count: NotSet,
+        }
+        .insert(db)
+        .await?;
+
+        let task_labels: Vec<task_label::ActiveModel> = task_label::Entity::find()
+            .filter(task_label::Column::TaskId.eq(self.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|task_label| task_label::ActiveModel {
+                id: NotSet,
+                task_id: Set(next_task.id),
+                label_id: Set(task_label.label_id),
+            })
+            .collect();
+
+        if !task_labels.is_empty() {
+            task_label::Entity::insert_many(task_labels)
+                .exec(db)
+                .await?;
+        }
+
+        series.occurrence_count = Set(occurrence_count + 1);
+        series.update(db).await?;
+
+        Ok(Some(next_task))
+    }
+}
+
+/// Unfinished tasks that any of `task_ids` wait on, leaving out the ones in `task_ids`.
+pub async fn find_open_blockers<C>(db: &C, task_ids: &[i32]) -> Result<Vec<Model>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let blocker_ids: Vec<i32> = task_dependency::Entity::find()
+        .select_only()
+        .column(task_dependency::Column::BlockedById)
+        .filter(task_dependency::Column::TaskId.is_in(task_ids.to_vec()))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Entity::find()
+        .filter(Column::Id.is_in(blocker_ids))
+        .filter(Column::Id.is_not_in(task_ids.to_vec()))
+        .filter(Column::Status.ne(TaskStatus::Done))
+        .all(db)
+        .await
+}
+
+/// A task matching a full-text search. `title`, `description` and `comments` hold the
+/// matching parts of the task with the search terms wrapped in `<mark>` tags, `rank`
+/// is higher for better matches.
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct SearchHit {
+    pub id: i32,
+    pub rank: f64,
+    pub title: String,
+    pub description: String,
+    pub comments: String,
+}
+
+/// Delimit matches in the raw search results, they are only turned into `<mark>` tags
+/// after the text around them is HTML escaped. Private use characters, so they don't
+/// clash with what users write.
+const MATCH_START: char = '\u{E000}';
+const MATCH_END: char = '\u{E001}';
+
+/// Tasks of `user_id` matching `query`, best matches first. Titles weigh more than
+/// descriptions, descriptions more than comments.
+///
+/// Postgres reads `query` as a web search (`"quoted phrases"`, `or`, `-excluded`),
+/// SQLite requires every word to be present.
+pub async fn search<C>(
+    db: &C,
+    user_id: i32,
+    query: &str,
+    limit: u64,
+    offset: u64,
+) -> Result<Vec<SearchHit>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+
+    let sql = match backend {
+        DatabaseBackend::Postgres => {
+            format!(
+                r#"SELECT task.id,
+                ts_rank(task.search_vector, query)::float8 AS rank,
+                ts_headline('english', task.title, query,
+                    'StartSel={MATCH_START}, StopSel={MATCH_END}, HighlightAll=true') AS title,
+                ts_headline('english', task.description, query,
+                    'StartSel={MATCH_START}, StopSel={MATCH_END}, MaxFragments=2') AS description,
+                ts_headline('english', coalesce(
+                    (SELECT string_agg(body, ' ') FROM task_comment
+                        WHERE task_comment.task_id = task.id),
+                    ''
+                ), query, 'StartSel={MATCH_START}, StopSel={MATCH_END}, MaxFragments=2') AS comments
+            FROM task, websearch_to_tsquery('english', $1) AS query
+            WHERE task.search_vector @@ query AND task.user_id = $2
+            ORDER BY rank DESC, task.date_created DESC
+            LIMIT $3 OFFSET $4"#
+            )
+        }
+        DatabaseBackend::Sqlite => {
+            format!(
+                r#"SELECT task.id AS id,
+                -bm25(task_search, 10.0, 5.0, 1.0) AS rank,
+                highlight(task_search, 0, '{MATCH_START}', '{MATCH_END}') AS title,
+                snippet(task_search, 1, '{MATCH_START}', '{MATCH_END}', '...', 32) AS description,
+                snippet(task_search, 2, '{MATCH_START}', '{MATCH_END}', '...', 32) AS comments
+            FROM task_search
+            INNER JOIN task ON task.id = task_search.rowid
+            WHERE task_search MATCH ? AND task.user_id = ?
+            ORDER BY rank DESC, task.date_created DESC
+            LIMIT ? OFFSET ?"#
+            )
+        }
+        DatabaseBackend::MySql => {
+            return Err(DbErr::Custom(
+                "Task search is not supported on MySQL.".to_string(),
+            ))
+        }
+    };
+
+    let Some(query) = search_query(backend, query) else {
+        return Ok(Vec::new());
+    };
+
+    let hits = SearchHit::find_by_statement(Statement::from_sql_and_values(
+        backend,
+        sql,
+        [
+            query,
+            user_id.into(),
+            (limit as i64).into(),
+            (offset as i64).into(),
+        ],
+    ))
+    .all(db)
+    .await?;
+
+    Ok(hits
+        .into_iter()
+        .map(|hit| SearchHit {
+            title: mark_matches(&hit.title),
+            description: mark_matches(&hit.description),
+            comments: mark_matches(&hit.comments),
+            ..hit
+        })
+        .collect())
+}
+
+/// HTML escapes a search result, then wraps the matches in `<mark>` tags. Task text
+/// is user input, so only the tags added here may end up as markup.
+fn mark_matches(text: &str) -> String {
+    let mut marked = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => marked.push_str("&amp;"),
+            '<' => marked.push_str("&lt;"),
+            '>' => marked.push_str("&gt;"),
+            '"' => marked.push_str("&quot;"),
+            '\'' => marked.push_str("&#39;"),
+            MATCH_START => marked.push_str("<mark>"),
+            MATCH_END => marked.push_str("</mark>"),
+            c => marked.push(c),
+        }
+    }
+
+    marked
+}
+
+/// Number of tasks of `user_id` matching `query`, see [`search`].
+pub async fn count_search_hits<C>(db: &C, user_id: i32, query: &str) -> Result<u64, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+
+    let sql = match backend {
+        DatabaseBackend::Postgres => {
+            r#"SELECT COUNT(*) AS count
+            FROM task, websearch_to_tsquery('english', $1) AS query
+            WHERE task.search_vector @@ query AND task.user_id = $2"#
+        }
+        DatabaseBackend::Sqlite => {
+            r#"SELECT COUNT(*) AS count
+            FROM task_search
+            INNER JOIN task ON task.id = task_search.rowid
+            WHERE task_search MATCH ? AND task.user_id = ?"#
+        }
+        DatabaseBackend::MySql => {
+            return Err(DbErr::Custom(
+                "Task search is not supported on MySQL.".to_string(),
+            ))
+        }
+    };
+
+    let Some(query) = search_query(backend, query) else {
+        return Ok(0);
+    };
+
+    let count = db
+        .query_one(Statement::from_sql_and_values(
+            backend,
+            sql,
+            [query, user_id.into()],
+        ))
+        .await?
+        .map(|row| row.try_get::<i64>("", "count"))
+        .transpose()?
+        .unwrap_or(0);
+
+    Ok(count.max(0) as u64)
+}
+
+/// The search text as the backend expects it, `None` when nothing is left to search
+/// for.
+fn search_query(backend: DatabaseBackend, query: &str) -> Option<Value> {
+    let query = match backend {
+        DatabaseBackend::Sqlite => fts5_query(query),
+        _ => query.trim().to_string(),
+    };
+
+    (!query.is_empty()).then(|| query.into())
+}
+
+/// Turns free text into an FTS5 query matching all of its words. Every word is quoted
+/// so operators and punctuation in the input are searched for instead of parsed.
+fn fts5_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fts5_query_quotes_every_word() {
+        assert_eq!(fts5_query("  write  docs "), r#""write" "docs""#);
+        assert_eq!(
+            fts5_query(r#"say "hi" OR -x*"#),
+            r#""say" """hi""" "OR" "-x*""#
+        );
+        assert_eq!(fts5_query("   "), "");
+    }
+
+    #[test]
+    fn matches_are_marked_in_escaped_text() {
+        assert_eq!(
+            mark_matches("<script>alert('x')</script> \u{E000}fix\u{E001} & \"ship\""),
+            "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; <mark>fix</mark> &amp; &quot;ship&quot;"
+        );
+    }
+}
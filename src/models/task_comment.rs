@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::task_comment::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,16 @@
+use sea_orm::{ActiveModelBehavior, Set};
+
+use super::_entities::task_series::ActiveModel;
+use crate::recurrence::{format_weekdays, RecurrenceRule};
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl ActiveModel {
+    pub fn set_rule(&mut self, rule: RecurrenceRule) {
+        self.frequency = Set(rule.frequency);
+        self.interval = Set(rule.interval as i32);
+        self.by_weekday = Set(format_weekdays(&rule.by_weekday));
+        self.until = Set(rule.until);
+        self.count = Set(rule.count.map(|count| count as i32));
+    }
+}
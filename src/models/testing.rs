@@ -0,0 +1,47 @@
+//! Databases for tests that need to run real queries.
+
+use sea_orm::{
+    ActiveModelTrait, ConnectionTrait, Database, DatabaseConnection, EntityTrait, Schema, Set,
+};
+
+use super::_entities::{
+    personal_access_token, refresh_token, revoked_token, task, task_series, user,
+};
+
+/// Connects to the SQLite database at `url` and creates the tables from the
+/// entities, since the migrations only run on Postgres.
+pub async fn database(url: &str) -> DatabaseConnection {
+    let db = Database::connect(url).await.unwrap();
+
+    create_table(&db, user::Entity).await;
+    create_table(&db, task_series::Entity).await;
+    create_table(&db, task::Entity).await;
+    create_table(&db, revoked_token::Entity).await;
+    create_table(&db, refresh_token::Entity).await;
+    create_table(&db, personal_access_token::Entity).await;
+
+    db
+}
+
+/// Inserts a user named `username`, with a password hash nothing verifies against.
+pub async fn user(db: &DatabaseConnection, username: &str) -> user::Model {
+    user::ActiveModel {
+        name: Set(username.to_string()),
+        username: Set(username.to_string()),
+        email: Set(format!("{}@example.com", username)),
+        password: Set("hash".to_string()),
+        role: Set(Default::default()),
+        failed_login_count: Set(0),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+async fn create_table<E: EntityTrait>(db: &DatabaseConnection, entity: E) {
+    let backend = db.get_database_backend();
+    let statement = Schema::new(backend).create_table_from_entity(entity);
+
+    db.execute(backend.build(&statement)).await.unwrap();
+}
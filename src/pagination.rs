@@ -0,0 +1,604 @@
+use std::{fmt, sync::Arc};
+
+use axum::{
+    extract::{FromRequestParts, OriginalUri, Query},
+    http::{request::Parts, Uri},
+};
+use hmac::{Hmac, Mac};
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, ColumnTrait, Condition, DatabaseConnection, DbErr,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, SelectorTrait,
+};
+use sha2::Sha256;
+
+use crate::{api_response::ResponseMetadata, config::PaginationConfig, error::AppError, AppState};
+
+/// `page`, `per_page` and `cursor` of a list request. Page size defaults to
+/// `pagination.per_page` and can't exceed `pagination.max_per_page`.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    pub page: u64,
+    pub per_page: u64,
+    pub cursor: Option<Cursor>,
+    uri: Uri,
+    key: CursorKey,
+    /// What cursors of this request are bound to, see [`cursor_context`].
+    context: String,
+}
+
+impl FromRequestParts<Arc<AppState>> for Pagination {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        // nested routers see their own path in `parts.uri`, links need the full one
+        let uri = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.clone(),
+            None => parts.uri.clone(),
+        };
+
+        Self::parse(uri, &state.config.pagination, &state.config.auth.jwt_secret)
+    }
+}
+
+impl Pagination {
+    fn parse(uri: Uri, config: &PaginationConfig, secret: &str) -> Result<Self, AppError> {
+        let max_per_page = config.max_per_page;
+
+        let Query(params) = Query::<Vec<(String, String)>>::try_from_uri(&uri)?;
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let page = match param("page") {
+            Some(page) => page
+                .parse::<u64>()
+                .ok()
+                .filter(|page| *page > 0)
+                .ok_or_else(|| {
+                    AppError::GenericError("page must be a positive number.".to_string())
+                })?,
+            None => 1,
+        };
+
+        let per_page = match param("per_page") {
+            Some(per_page) => per_page
+                .parse::<u64>()
+                .ok()
+                .filter(|per_page| (1..=max_per_page).contains(per_page))
+                .ok_or_else(|| {
+                    AppError::GenericError(format!(
+                        "per_page must be between 1 and {}.",
+                        max_per_page
+                    ))
+                })?,
+            None => config.per_page.min(max_per_page),
+        };
+
+        let key = CursorKey::derive(secret);
+        let context = cursor_context(uri.path(), &params);
+
+        let cursor = param("cursor")
+            .map(|cursor| Cursor::decode(cursor, &key, &context))
+            .transpose()?;
+
+        Ok(Self {
+            page,
+            per_page,
+            cursor,
+            uri,
+            key,
+            context,
+        })
+    }
+
+    /// Metadata of a list with `count` rows in total, linking to the neighbouring pages
+    /// by cursor when the request used one and by page number otherwise.
+    pub fn metadata(
+        &self,
+        count: u64,
+        next_cursor: Option<Cursor>,
+        prev_cursor: Option<Cursor>,
+    ) -> ResponseMetadata {
+        let next_cursor = next_cursor.map(|cursor| cursor.encode(&self.key, &self.context));
+        let prev_cursor = prev_cursor.map(|cursor| cursor.encode(&self.key, &self.context));
+        let total_page = count.div_ceil(self.per_page);
+
+        let (previous_url, next_url) = if self.cursor.is_some() {
+            (
+                prev_cursor
+                    .as_deref()
+                    .map(|cursor| self.url_with("cursor", cursor)),
+                next_cursor
+                    .as_deref()
+                    .map(|cursor| self.url_with("cursor", cursor)),
+            )
+        } else {
+            (
+                (self.page > 1).then(|| self.page_url(self.page - 1)),
+                (self.page < total_page).then(|| self.page_url(self.page + 1)),
+            )
+        };
+
+        ResponseMetadata {
+            count,
+            per_page: self.per_page,
+            total_page,
+            first_page_url: Some(self.page_url(1)),
+            last_page_url: Some(self.page_url(total_page.max(1))),
+            previous_url,
+            current_url: Some(self.uri.to_string()),
+            next_url,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+
+    /// Fetches the page of `query` this request asks for, by [`keyset`] next to the
+    /// cursor if it has one and by page number otherwise. `count` is the number of rows
+    /// of `query` and `key` gives the `(date_created, id)` of a row.
+    pub async fn fetch<'db, Q, C, K>(
+        &self,
+        db: &'db DatabaseConnection,
+        query: Q,
+        count: u64,
+        (date_created, id): (C, C),
+        descending: bool,
+        key: K,
+    ) -> Result<CursorPage<<Q::Selector as SelectorTrait>::Item>, DbErr>
+    where
+        Q: PaginatorTrait<'db, DatabaseConnection> + QueryFilter + QueryOrder + QuerySelect + Send,
+        C: ColumnTrait,
+        K: Fn(&<Q::Selector as SelectorTrait>::Item) -> (DateTimeWithTimeZone, i32),
+    {
+        let query = keyset(
+            query,
+            self.cursor.as_ref(),
+            date_created,
+            id,
+            descending,
+            self.per_page,
+        );
+
+        match &self.cursor {
+            Some(cursor) => {
+                let rows = query.paginate(db, self.per_page + 1).fetch_page(0).await?;
+
+                Ok(CursorPage::new(rows, Some(cursor), self.per_page, key))
+            }
+            None => {
+                let rows = query
+                    .paginate(db, self.per_page)
+                    .fetch_page(self.page - 1)
+                    .await?;
+
+                Ok(CursorPage::from_page(
+                    rows,
+                    self.page,
+                    count.div_ceil(self.per_page),
+                    key,
+                ))
+            }
+        }
+    }
+
+    fn page_url(&self, page: u64) -> String {
+        self.url_with("page", &page.to_string())
+    }
+
+    /// The request URL pointing at another page, other query parameters are kept.
+    fn url_with(&self, name: &str, value: &str) -> String {
+        let mut params: Vec<(String, String)> = Query::try_from_uri(&self.uri)
+            .map(|Query(params)| params)
+            .unwrap_or_default();
+
+        params.retain(|(key, _)| key != "page" && key != "cursor");
+        params.push((name.to_string(), value.to_string()));
+
+        format!(
+            "{}?{}",
+            self.uri.path(),
+            serde_urlencoded::to_string(&params).unwrap_or_default()
+        )
+    }
+}
+
+/// The path and filters of a list request, everything but the position and page size.
+/// A cursor only works for the list it was handed out for, another endpoint or other
+/// filters would read it against a different ordering or set of rows.
+fn cursor_context(path: &str, params: &[(String, String)]) -> String {
+    let mut filters: Vec<&(String, String)> = params
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "page" | "per_page" | "cursor"))
+        .collect();
+    filters.sort();
+
+    let mut context = path.to_string();
+
+    for (key, value) in filters {
+        context.push('&');
+        context.push_str(&serde_urlencoded::to_string([(key, value)]).unwrap_or_default());
+    }
+
+    context
+}
+
+/// Signs cursors. Derived from the JWT secret rather than using it directly, so a
+/// cursor signature can't stand in for anything else signed with the secret.
+#[derive(Clone)]
+pub struct CursorKey([u8; 32]);
+
+impl CursorKey {
+    pub fn derive(secret: &str) -> Self {
+        let mut mac: Hmac<Sha256> =
+            Hmac::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+        mac.update(b"pagination-cursor");
+
+        Self(mac.finalize().into_bytes().into())
+    }
+}
+
+// keeps the key out of logs
+impl fmt::Debug for CursorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CursorKey(***)")
+    }
+}
+
+/// Which side of the cursor a page lies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorDirection {
+    After,
+    Before,
+}
+
+/// Position in a list ordered by `(date_created, id)`. Handed out to clients as an
+/// opaque string signed with a [`CursorKey`] together with the list it belongs to, so
+/// a cursor can't be forged to point at arbitrary rows or be replayed on another list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub date_created: DateTimeWithTimeZone,
+    pub id: i32,
+    pub direction: CursorDirection,
+}
+
+impl Cursor {
+    pub fn after(date_created: DateTimeWithTimeZone, id: i32) -> Self {
+        Self {
+            date_created,
+            id,
+            direction: CursorDirection::After,
+        }
+    }
+
+    pub fn before(date_created: DateTimeWithTimeZone, id: i32) -> Self {
+        Self {
+            date_created,
+            id,
+            direction: CursorDirection::Before,
+        }
+    }
+
+    pub fn encode(&self, key: &CursorKey, context: &str) -> String {
+        let direction = match self.direction {
+            CursorDirection::After => "a",
+            CursorDirection::Before => "b",
+        };
+        let payload = format!(
+            "{}|{}|{}",
+            direction,
+            self.date_created.to_rfc3339(),
+            self.id
+        );
+        let signature = hex::encode(
+            Self::mac(payload.as_bytes(), key, context)
+                .finalize()
+                .into_bytes(),
+        );
+
+        format!("{}.{}", hex::encode(payload), signature)
+    }
+
+    pub fn decode(value: &str, key: &CursorKey, context: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::GenericError("Invalid cursor.".to_string());
+
+        let (payload, signature) = value.split_once('.').ok_or_else(invalid)?;
+        let payload = hex::decode(payload).map_err(|_| invalid())?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+
+        Self::mac(&payload, key, context)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let payload = String::from_utf8(payload).map_err(|_| invalid())?;
+        let mut parts = payload.splitn(3, '|');
+
+        let direction = match parts.next() {
+            Some("a") => CursorDirection::After,
+            Some("b") => CursorDirection::Before,
+            _ => return Err(invalid()),
+        };
+        let date_created = parts
+            .next()
+            .and_then(|date| DateTimeWithTimeZone::parse_from_rfc3339(date).ok())
+            .ok_or_else(invalid)?;
+        let id = parts
+            .next()
+            .and_then(|id| id.parse::<i32>().ok())
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            date_created,
+            id,
+            direction,
+        })
+    }
+
+    fn mac(payload: &[u8], key: &CursorKey, context: &str) -> Hmac<Sha256> {
+        let mut mac: Hmac<Sha256> =
+            Hmac::new_from_slice(&key.0).expect("HMAC can take key of any size");
+        // the context can't contain a newline, it's URL encoded
+        mac.update(context.as_bytes());
+        mac.update(b"\n");
+        mac.update(payload);
+        mac
+    }
+}
+
+/// Restricts `query` to the `per_page` rows next to `cursor`, plus one to tell whether
+/// there are more. The list is ordered by `date_created` then `id`, newest first when
+/// `descending`, after any order `query` already has. Without a cursor this is the
+/// first page.
+fn keyset<Q, C>(
+    query: Q,
+    cursor: Option<&Cursor>,
+    date_created: C,
+    id: C,
+    descending: bool,
+    per_page: u64,
+) -> Q
+where
+    Q: QueryFilter + QueryOrder + QuerySelect,
+    C: ColumnTrait,
+{
+    // pages before the cursor are read in reverse and flipped back by `CursorPage`
+    let backwards = cursor.is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+    let read_descending = descending != backwards;
+
+    let mut query = query;
+
+    if let Some(cursor) = cursor {
+        let (date_cmp, id_cmp) = if read_descending {
+            (date_created.lt(cursor.date_created), id.lt(cursor.id))
+        } else {
+            (date_created.gt(cursor.date_created), id.gt(cursor.id))
+        };
+
+        query = query.filter(
+            Condition::any().add(date_cmp).add(
+                Condition::all()
+                    .add(date_created.eq(cursor.date_created))
+                    .add(id_cmp),
+            ),
+        );
+    }
+
+    let order = if read_descending {
+        sea_orm::Order::Desc
+    } else {
+        sea_orm::Order::Asc
+    };
+
+    query
+        .order_by(date_created, order.clone())
+        .order_by(id, order)
+        .limit(per_page + 1)
+}
+
+/// One page of rows fetched with [`keyset`], with the cursors of its neighbours.
+#[derive(Debug)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl<T> CursorPage<T> {
+    /// `key` gives the `(date_created, id)` of a row.
+    pub fn new<K>(mut rows: Vec<T>, cursor: Option<&Cursor>, per_page: u64, key: K) -> Self
+    where
+        K: Fn(&T) -> (DateTimeWithTimeZone, i32),
+    {
+        let backwards = cursor.is_some_and(|cursor| cursor.direction == CursorDirection::Before);
+        let has_more = rows.len() as u64 > per_page;
+
+        rows.truncate(per_page as usize);
+
+        if backwards {
+            rows.reverse();
+        }
+
+        let (has_next, has_prev) = if backwards {
+            (true, has_more)
+        } else {
+            (has_more, cursor.is_some())
+        };
+
+        Self::with_cursors(rows, has_next, has_prev, key)
+    }
+
+    /// Cursors for a page fetched by page number, so clients can switch to cursors.
+    pub fn from_page<K>(items: Vec<T>, page: u64, total_page: u64, key: K) -> Self
+    where
+        K: Fn(&T) -> (DateTimeWithTimeZone, i32),
+    {
+        Self::with_cursors(items, page < total_page, page > 1, key)
+    }
+
+    fn with_cursors<K>(items: Vec<T>, has_next: bool, has_prev: bool, key: K) -> Self
+    where
+        K: Fn(&T) -> (DateTimeWithTimeZone, i32),
+    {
+        let cursor_at = |item: Option<&T>, cursor: fn(DateTimeWithTimeZone, i32) -> Cursor| {
+            item.map(|item| {
+                let (date_created, id) = key(item);
+                cursor(date_created, id)
+            })
+        };
+
+        let next_cursor = has_next
+            .then(|| cursor_at(items.last(), Cursor::after))
+            .flatten();
+        let prev_cursor = has_prev
+            .then(|| cursor_at(items.first(), Cursor::before))
+            .flatten();
+
+        Self {
+            items,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    fn config(per_page: u64, max_per_page: u64) -> PaginationConfig {
+        PaginationConfig {
+            per_page,
+            max_per_page,
+        }
+    }
+
+    fn date(value: &str) -> DateTimeWithTimeZone {
+        DateTimeWithTimeZone::parse_from_rfc3339(value).unwrap()
+    }
+
+    #[test]
+    fn links_keep_the_other_query_parameters() {
+        let uri: Uri = "/api/tasks?status=done&page=2&per_page=5".parse().unwrap();
+        let pagination = Pagination::parse(uri, &config(10, 50), SECRET).unwrap();
+
+        assert_eq!(pagination.page, 2);
+        assert_eq!(pagination.per_page, 5);
+
+        let metadata = pagination.metadata(12, None, None);
+
+        assert_eq!(metadata.total_page, 3);
+        assert_eq!(
+            metadata.first_page_url.as_deref(),
+            Some("/api/tasks?status=done&per_page=5&page=1")
+        );
+        assert_eq!(
+            metadata.previous_url.as_deref(),
+            Some("/api/tasks?status=done&per_page=5&page=1")
+        );
+        assert_eq!(
+            metadata.next_url.as_deref(),
+            Some("/api/tasks?status=done&per_page=5&page=3")
+        );
+        assert_eq!(
+            metadata.last_page_url.as_deref(),
+            Some("/api/tasks?status=done&per_page=5&page=3")
+        );
+    }
+
+    #[test]
+    fn page_size_is_bounded() {
+        let parse = |uri: &str| Pagination::parse(uri.parse().unwrap(), &config(10, 50), SECRET);
+
+        assert_eq!(parse("/api/tasks").unwrap().per_page, 10);
+        assert!(parse("/api/tasks?per_page=51").is_err());
+        assert!(parse("/api/tasks?per_page=0").is_err());
+        assert!(parse("/api/tasks?page=0").is_err());
+    }
+
+    #[test]
+    fn cursor_round_trips_and_rejects_tampering() {
+        let key = CursorKey::derive(SECRET);
+        let context = "/api/tasks";
+        let cursor = Cursor::before(date("2025-02-01T10:30:00.123456+01:00"), 42);
+        let encoded = cursor.encode(&key, context);
+
+        assert_eq!(Cursor::decode(&encoded, &key, context).unwrap(), cursor);
+        assert!(Cursor::decode(&encoded, &CursorKey::derive("other-secret"), context).is_err());
+
+        let (payload, signature) = encoded.split_once('.').unwrap();
+        let forged = hex::encode(
+            String::from_utf8(hex::decode(payload).unwrap())
+                .unwrap()
+                .replace("|42", "|43"),
+        );
+
+        assert!(Cursor::decode(&format!("{}.{}", forged, signature), &key, context).is_err());
+        assert!(Cursor::decode("not-a-cursor", &key, context).is_err());
+    }
+
+    #[test]
+    fn cursors_only_work_for_their_list() {
+        let parse = |uri: &str| Pagination::parse(uri.parse().unwrap(), &config(10, 50), SECRET);
+
+        let first = parse("/api/tasks?status=done&label=work&per_page=5").unwrap();
+        let metadata = first.metadata(
+            20,
+            Some(Cursor::after(date("2025-01-01T00:00:00Z"), 7)),
+            None,
+        );
+        let cursor = metadata.next_cursor.unwrap();
+
+        // the order of the filters and the page size don't matter
+        let next = parse(&format!(
+            "/api/tasks?label=work&status=done&cursor={cursor}"
+        ))
+        .unwrap();
+        assert_eq!(next.cursor.unwrap().id, 7);
+
+        assert!(parse(&format!(
+            "/api/tasks?status=todo&label=work&cursor={cursor}"
+        ))
+        .is_err());
+        assert!(parse(&format!(
+            "/api/users?status=done&label=work&cursor={cursor}"
+        ))
+        .is_err());
+
+        // nor is it a valid signature under the plain secret
+        let (payload, signature) = cursor.split_once('.').unwrap();
+        let mut mac: Hmac<Sha256> = Hmac::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(&hex::decode(payload).unwrap());
+        assert!(mac.verify_slice(&hex::decode(signature).unwrap()).is_err());
+    }
+
+    #[test]
+    fn pages_before_a_cursor_are_flipped_back() {
+        let key = |id: &i32| (date("2025-01-01T00:00:00Z"), *id);
+        let cursor = Cursor::before(date("2025-01-01T00:00:00Z"), 10);
+
+        // read in reverse, one row more than a page
+        let page = CursorPage::new(vec![11, 12, 13], Some(&cursor), 2, key);
+
+        assert_eq!(page.items, vec![12, 11]);
+        assert_eq!(
+            page.next_cursor,
+            Some(Cursor::after(date("2025-01-01T00:00:00Z"), 11))
+        );
+        assert_eq!(
+            page.prev_cursor,
+            Some(Cursor::before(date("2025-01-01T00:00:00Z"), 12))
+        );
+
+        let first_page = CursorPage::new(vec![5, 4], None, 2, key);
+
+        assert!(first_page.next_cursor.is_none());
+        assert!(first_page.prev_cursor.is_none());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use super::{Decision, Quota, RateLimitStore};
+
+/// How often buckets that have filled up again are dropped, a full bucket is the same
+/// as a missing one.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keeps the buckets in this process.
+#[derive(Debug)]
+pub struct InMemoryStore {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    buckets: HashMap<String, Bucket>,
+    last_sweep: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    quota: Quota,
+    updated_at: Instant,
+}
+
+impl Default for InMemoryStore {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                buckets: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl InMemoryStore {
+    fn acquire_at(&self, key: &str, quota: Quota, now: Instant) -> Decision {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+
+        if now.duration_since(state.last_sweep) >= SWEEP_INTERVAL {
+            state.buckets.retain(|_, bucket| !bucket.is_full(now));
+            state.last_sweep = now;
+        }
+
+        let bucket = state
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: quota.requests as f64,
+                quota,
+                updated_at: now,
+            });
+
+        // a changed budget applies from now on
+        bucket.quota = quota;
+        bucket.refill(now);
+
+        let allowed = bucket.tokens >= 1.0;
+
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let rate = quota.refill_rate();
+
+        Decision {
+            allowed,
+            limit: quota.requests,
+            remaining: bucket.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64((quota.requests as f64 - bucket.tokens) / rate),
+            retry_after: if allowed {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+            },
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+
+        self.tokens =
+            (self.tokens + elapsed * self.quota.refill_rate()).min(self.quota.requests as f64);
+        self.updated_at = now;
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let mut bucket = *self;
+        bucket.refill(now);
+        bucket.tokens >= self.quota.requests as f64
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryStore {
+    async fn acquire(&self, key: &str, quota: Quota) -> io::Result<Decision> {
+        Ok(self.acquire_at(key, quota, Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_up_to_the_budget_then_refills() {
+        let store = InMemoryStore::default();
+        let quota = Quota::new(2, 10);
+        let start = Instant::now();
+
+        assert!(store.acquire_at("ip:1", quota, start).allowed);
+
+        let second = store.acquire_at("ip:1", quota, start);
+        assert!(second.allowed);
+        assert_eq!(second.remaining, 0);
+        assert_eq!(second.reset_after, Duration::from_secs(10));
+
+        let denied = store.acquire_at("ip:1", quota, start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Duration::from_secs(5));
+
+        // other keys have their own bucket
+        assert!(store.acquire_at("ip:2", quota, start).allowed);
+
+        // one token every 5 seconds
+        assert!(
+            store
+                .acquire_at("ip:1", quota, start + Duration::from_secs(5))
+                .allowed
+        );
+        assert!(
+            !store
+                .acquire_at("ip:1", quota, start + Duration::from_secs(5))
+                .allowed
+        );
+    }
+
+    #[test]
+    fn full_buckets_are_swept() {
+        let store = InMemoryStore::default();
+        let start = store.state.lock().unwrap().last_sweep;
+
+        store.acquire_at("ip:1", Quota::new(1, 10), start);
+        store.acquire_at("ip:2", Quota::new(1, 100), start);
+        store.acquire_at("ip:3", Quota::new(1, 1), start + SWEEP_INTERVAL);
+
+        let state = store.state.lock().unwrap();
+        let mut keys: Vec<_> = state.buckets.keys().collect();
+        keys.sort();
+
+        assert_eq!(keys, vec!["ip:2", "ip:3"]);
+    }
+}
@@ -0,0 +1,49 @@
+use std::{fmt::Debug, io, time::Duration};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+pub mod memory;
+
+/// Where the token buckets of the rate limiter are kept. The in-memory store limits
+/// each instance on its own, a shared one makes the budgets hold across instances.
+#[async_trait]
+pub trait RateLimitStore: Debug + Send + Sync {
+    /// Takes a token from the bucket of `key`, which starts out full.
+    async fn acquire(&self, key: &str, quota: Quota) -> io::Result<Decision>;
+}
+
+/// A budget of `requests` per `period_seconds`. Buckets hold that many tokens and
+/// refill continuously, so bursts up to the full budget are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    pub requests: u32,
+    pub period_seconds: u64,
+}
+
+impl Quota {
+    pub const fn new(requests: u32, period_seconds: u64) -> Self {
+        Self {
+            requests,
+            period_seconds,
+        }
+    }
+
+    /// Tokens added per second.
+    fn refill_rate(&self) -> f64 {
+        self.requests as f64 / self.period_seconds as f64
+    }
+}
+
+/// Outcome of [`RateLimitStore::acquire`], with what the `RateLimit-*` headers need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset_after: Duration,
+    /// Until the next request would be allowed, zero when this one was.
+    pub retry_after: Duration,
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, Datelike, Days, FixedOffset, Months, Weekday};
+
+use crate::models::_entities::{sea_orm_active_enums::RecurrenceFrequency, task_series};
+
+/// Upper bound on the candidates looked at for one occurrence, a rule that can't
+/// match (e.g. daily every 7 days on a weekday the start never falls on) ends the
+/// series instead of looping forever.
+const MAX_CANDIDATES: u32 = 1000;
+
+/// An RRULE-like schedule: every `interval` days, weeks or months, optionally only on
+/// `by_weekday`, ending at `until` or after `count` occurrences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: RecurrenceFrequency,
+    pub interval: u32,
+    pub by_weekday: Vec<Weekday>,
+    pub until: Option<DateTime<FixedOffset>>,
+    pub count: Option<u32>,
+}
+
+impl RecurrenceRule {
+    /// The first occurrence strictly after `after` in a series anchored at
+    /// `starts_at` that already has `occurrences` occurrences, `None` once the series
+    /// is over.
+    ///
+    /// Occurrences are counted from `starts_at` rather than from `after`, so monthly
+    /// series on the 31st don't drift to the 28th after February.
+    pub fn next_occurrence(
+        &self,
+        starts_at: DateTime<FixedOffset>,
+        after: DateTime<FixedOffset>,
+        occurrences: u32,
+    ) -> Option<DateTime<FixedOffset>> {
+        if self.count.is_some_and(|count| occurrences >= count) {
+            return None;
+        }
+
+        let interval = self.interval.max(1);
+
+        let next = match self.frequency {
+            RecurrenceFrequency::Daily => {
+                let elapsed = (after - starts_at).num_days().max(0) as u32;
+
+                (elapsed / interval..elapsed / interval + MAX_CANDIDATES)
+                    .filter_map(|step| {
+                        starts_at.checked_add_days(Days::new((step * interval).into()))
+                    })
+                    .find(|candidate| *candidate > after && self.matches_weekday(*candidate))
+            }
+            RecurrenceFrequency::Weekly => {
+                // weeks are numbered from the Monday of the week the series starts in
+                let offset = starts_at.weekday().num_days_from_monday();
+                let elapsed = (after - starts_at).num_days().max(0) as u32;
+                let weekdays = if self.by_weekday.is_empty() {
+                    vec![starts_at.weekday()]
+                } else {
+                    self.by_weekday.clone()
+                };
+
+                // the week `after` falls in, or the next one the series runs in
+                let week = ((elapsed + offset) / 7).div_ceil(interval) * interval;
+
+                // every weekday of the week after that one is past `after`, so one of
+                // the two has the occurrence
+                [week, week + interval]
+                    .into_iter()
+                    .flat_map(|week| (0..7).map(move |weekday| week * 7 + weekday))
+                    .filter_map(|day| day.checked_sub(offset))
+                    .filter_map(|day| starts_at.checked_add_days(Days::new(day.into())))
+                    .find(|candidate| *candidate > after && weekdays.contains(&candidate.weekday()))
+            }
+            RecurrenceFrequency::Monthly => {
+                let elapsed = (after.year() - starts_at.year()) * 12 + after.month() as i32
+                    - starts_at.month() as i32;
+                let first_step = elapsed.max(0) as u32 / interval;
+
+                (first_step..first_step + MAX_CANDIDATES)
+                    .filter_map(|step| starts_at.checked_add_months(Months::new(step * interval)))
+                    .find(|candidate| *candidate > after)
+            }
+        }?;
+
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+
+    fn matches_weekday(&self, date: DateTime<FixedOffset>) -> bool {
+        self.by_weekday.is_empty() || self.by_weekday.contains(&date.weekday())
+    }
+}
+
+impl From<&task_series::Model> for RecurrenceRule {
+    fn from(series: &task_series::Model) -> Self {
+        Self {
+            frequency: series.frequency,
+            interval: series.interval.max(1) as u32,
+            by_weekday: parse_weekdays(series.by_weekday.as_deref()),
+            until: series.until,
+            count: series.count.map(|count| count.max(0) as u32),
+        }
+    }
+}
+
+/// Weekdays are stored as a comma separated list, e.g. `mon,wed,fri`.
+pub fn parse_weekdays(by_weekday: Option<&str>) -> Vec<Weekday> {
+    by_weekday
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|weekday| weekday.trim().parse().ok())
+        .collect()
+}
+
+pub fn format_weekdays(by_weekday: &[Weekday]) -> Option<String> {
+    if by_weekday.is_empty() {
+        return None;
+    }
+
+    Some(
+        by_weekday
+            .iter()
+            .map(|weekday| weekday.to_string().to_lowercase())
+            .collect::<Vec<String>>()
+            .join(","),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("{date}T09:00:00+00:00")).unwrap()
+    }
+
+    fn rule(frequency: RecurrenceFrequency, interval: u32) -> RecurrenceRule {
+        RecurrenceRule {
+            frequency,
+            interval,
+            by_weekday: Vec::new(),
+            until: None,
+            count: None,
+        }
+    }
+
+    #[test]
+    fn daily_and_weekly() {
+        let start = at("2025-01-06"); // a Monday
+
+        let every_other_day = rule(RecurrenceFrequency::Daily, 2);
+        assert_eq!(
+            every_other_day.next_occurrence(start, start, 1),
+            Some(at("2025-01-08"))
+        );
+
+        let weekdays = RecurrenceRule {
+            by_weekday: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            ..rule(RecurrenceFrequency::Daily, 1)
+        };
+        assert_eq!(
+            weekdays.next_occurrence(start, at("2025-01-10"), 5),
+            Some(at("2025-01-13"))
+        );
+
+        let fortnightly = RecurrenceRule {
+            by_weekday: vec![Weekday::Mon, Weekday::Fri],
+            ..rule(RecurrenceFrequency::Weekly, 2)
+        };
+        assert_eq!(
+            fortnightly.next_occurrence(start, start, 1),
+            Some(at("2025-01-10"))
+        );
+        assert_eq!(
+            fortnightly.next_occurrence(start, at("2025-01-10"), 2),
+            Some(at("2025-01-20"))
+        );
+    }
+
+    #[test]
+    fn weekly_with_long_intervals() {
+        let start = at("2025-01-08"); // a Wednesday
+
+        let every_200_weeks = rule(RecurrenceFrequency::Weekly, 200);
+        let next = start.checked_add_days(Days::new(200 * 7)).unwrap();
+        assert_eq!(every_200_weeks.next_occurrence(start, start, 1), Some(next));
+
+        // the days before the start in its first week don't count
+        let mondays_and_fridays = RecurrenceRule {
+            by_weekday: vec![Weekday::Mon, Weekday::Fri],
+            ..rule(RecurrenceFrequency::Weekly, 365)
+        };
+        assert_eq!(
+            mondays_and_fridays.next_occurrence(start, start, 1),
+            Some(at("2025-01-10"))
+        );
+        assert_eq!(
+            mondays_and_fridays.next_occurrence(start, at("2025-01-10"), 2),
+            start.checked_add_days(Days::new(365 * 7 - 2))
+        );
+    }
+
+    #[test]
+    fn monthly_keeps_the_day_of_month() {
+        let start = at("2025-01-31");
+        let monthly = rule(RecurrenceFrequency::Monthly, 1);
+
+        let february = monthly.next_occurrence(start, start, 1).unwrap();
+        assert_eq!(february, at("2025-02-28"));
+        assert_eq!(
+            monthly.next_occurrence(start, february, 2),
+            Some(at("2025-03-31"))
+        );
+    }
+
+    #[test]
+    fn series_ends_at_until_or_count() {
+        let start = at("2025-01-06");
+
+        let until = RecurrenceRule {
+            until: Some(at("2025-01-07")),
+            ..rule(RecurrenceFrequency::Daily, 1)
+        };
+        assert_eq!(
+            until.next_occurrence(start, start, 1),
+            Some(at("2025-01-07"))
+        );
+        assert_eq!(until.next_occurrence(start, at("2025-01-07"), 2), None);
+
+        let count = RecurrenceRule {
+            count: Some(2),
+            ..rule(RecurrenceFrequency::Weekly, 1)
+        };
+        assert_eq!(
+            count.next_occurrence(start, start, 1),
+            Some(at("2025-01-13"))
+        );
+        assert_eq!(count.next_occurrence(start, at("2025-01-13"), 2), None);
+    }
+
+    #[test]
+    fn weekdays_round_trip() {
+        let weekdays = parse_weekdays(Some("mon, Wed,friday,nope"));
+
+        assert_eq!(weekdays, vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]);
+        assert_eq!(format_weekdays(&weekdays).as_deref(), Some("mon,wed,fri"));
+        assert_eq!(format_weekdays(&[]), None);
+    }
+}
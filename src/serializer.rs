@@ -1,6 +1,16 @@
+use std::collections::HashMap;
+
 use serde::Serialize;
 
-use crate::models::_entities::{label, task, user, user_profile};
+use crate::auth::scope::Scope;
+use crate::models::_entities::{
+    attachment, label, login_attempt, personal_access_token,
+    sea_orm_active_enums::{
+        LoginFailureReason, RecurrenceFrequency, TaskPriority, TaskStatus, UserRole,
+    },
+    task, task_comment, task_series, user, user_profile,
+};
+use crate::models::task::SearchHit;
 
 #[derive(Debug, Serialize)]
 pub struct UserSerializer {
@@ -8,8 +18,10 @@ pub struct UserSerializer {
     pub name: String,
     pub username: String,
     pub email: String,
+    pub role: UserRole,
     pub date_created: String,
     pub date_updated: Option<String>,
+    pub locked_until: Option<String>,
 }
 
 impl From<user::Model> for UserSerializer {
@@ -19,8 +31,67 @@ impl From<user::Model> for UserSerializer {
             name: value.name,
             username: value.username,
             email: value.email,
+            role: value.role,
             date_created: value.date_created.to_string(),
             date_updated: value.date_updated.map(|v| v.to_string()),
+            locked_until: value.locked_until.map(|v| v.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginAttemptSerializer {
+    pub id: i32,
+    pub username: String,
+    pub user_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub succeeded: bool,
+    pub failure_reason: Option<LoginFailureReason>,
+    pub date_created: String,
+}
+
+impl From<login_attempt::Model> for LoginAttemptSerializer {
+    fn from(value: login_attempt::Model) -> Self {
+        Self {
+            id: value.id,
+            username: value.username,
+            user_id: value.user_id,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            succeeded: value.succeeded,
+            failure_reason: value.failure_reason,
+            date_created: value.date_created.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PersonalAccessTokenSerializer {
+    pub id: i32,
+    pub name: String,
+    /// The start of the token, enough to recognise it.
+    pub token_prefix: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub date_created: String,
+    /// Only set when the token is created, it can't be retrieved afterwards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<personal_access_token::Model> for PersonalAccessTokenSerializer {
+    fn from(value: personal_access_token::Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            token_prefix: value.token_prefix,
+            scopes: Scope::split(&value.scopes),
+            expires_at: value.expires_at.map(|v| v.to_string()),
+            last_used_at: value.last_used_at.map(|v| v.to_string()),
+            date_created: value.date_created.to_string(),
+            token: None,
         }
     }
 }
@@ -48,8 +119,10 @@ pub struct UserWithProfileSerializer {
     pub name: String,
     pub username: String,
     pub email: String,
+    pub role: UserRole,
     pub date_created: String,
     pub date_updated: Option<String>,
+    pub locked_until: Option<String>,
     pub profile: Option<UserProfileSerializer>,
 }
 
@@ -64,8 +137,10 @@ impl From<(user::Model, Option<user_profile::Model>)> for UserWithProfileSeriali
             name: user.name,
             username: user.username,
             email: user.email,
+            role: user.role,
             date_created: user.date_created.to_string(),
             date_updated: user.date_updated.map(|v| v.to_string()),
+            locked_until: user.locked_until.map(|v| v.to_string()),
             profile: profile_serializer,
         }
     }
@@ -76,9 +151,12 @@ pub struct TaskSerializer {
     pub id: i32,
     pub title: String,
     pub description: String,
-    pub status: String,
-    pub priority: String,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
     pub uuid: String,
+    pub parent_id: Option<i32>,
+    pub series_id: Option<i32>,
+    pub comment_count: i32,
     pub due_date: Option<String>,
     pub date_created: String,
     pub date_updated: Option<String>,
@@ -93,6 +171,9 @@ impl From<task::Model> for TaskSerializer {
             status: value.status,
             priority: value.priority,
             uuid: value.uuid,
+            parent_id: value.parent_id,
+            series_id: value.series_id,
+            comment_count: value.comment_count,
             due_date: value.due_date.map(|v| v.to_string()),
             date_created: value.date_created.to_string(),
             date_updated: value.date_updated.map(|v| v.to_string()),
@@ -100,10 +181,210 @@ impl From<task::Model> for TaskSerializer {
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct TaskSearchResultSerializer {
+    #[serde(flatten)]
+    pub task: TaskSerializer,
+    pub rank: f64,
+    pub highlights: TaskHighlightsSerializer,
+}
+
+/// Parts of the task matching the search, HTML escaped with the matches wrapped in
+/// `<mark>` tags. Fields without a match are `null`.
+#[derive(Debug, Serialize)]
+pub struct TaskHighlightsSerializer {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub comments: Option<String>,
+}
+
+impl From<(task::Model, SearchHit)> for TaskSearchResultSerializer {
+    fn from((task, hit): (task::Model, SearchHit)) -> Self {
+        let highlight = |text: String| Some(text).filter(|text| text.contains("<mark>"));
+
+        Self {
+            task: TaskSerializer::from(task),
+            rank: hit.rank,
+            highlights: TaskHighlightsSerializer {
+                title: highlight(hit.title),
+                description: highlight(hit.description),
+                comments: highlight(hit.comments),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct FullTaskSerializer {
     pub task: TaskSerializer,
     pub labels: Vec<LabelSerializer>,
+    /// Percentage of the task tree that is done, see `TaskTreeSerializer::completion`.
+    pub completion: u8,
+    pub subtasks: Vec<TaskTreeSerializer>,
+    pub blocked_by: Vec<TaskSerializer>,
+    pub blocks: Vec<TaskSerializer>,
+    pub recurrence: Option<TaskSeriesSerializer>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttachmentSerializer {
+    pub uuid: String,
+    pub task_id: i32,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    /// Hex encoded SHA-256 of the content.
+    pub checksum: String,
+    pub date_created: String,
+}
+
+impl From<attachment::Model> for AttachmentSerializer {
+    fn from(value: attachment::Model) -> Self {
+        Self {
+            uuid: value.uuid,
+            task_id: value.task_id,
+            filename: value.filename,
+            content_type: value.content_type,
+            size: value.size,
+            checksum: value.checksum,
+            date_created: value.date_created.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentSerializer {
+    pub id: i32,
+    pub task_id: i32,
+    pub body: String,
+    pub edited: bool,
+    pub author: Option<CommentAuthorSerializer>,
+    pub date_created: String,
+    pub date_updated: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentAuthorSerializer {
+    pub id: i32,
+    pub name: String,
+    pub username: String,
+}
+
+impl From<(task_comment::Model, Option<user::Model>)> for CommentSerializer {
+    fn from((comment, author): (task_comment::Model, Option<user::Model>)) -> Self {
+        Self {
+            id: comment.id,
+            task_id: comment.task_id,
+            body: comment.body,
+            edited: comment.edited,
+            author: author.map(|author| CommentAuthorSerializer {
+                id: author.id,
+                name: author.name,
+                username: author.username,
+            }),
+            date_created: comment.date_created.to_string(),
+            date_updated: comment.date_updated.map(|v| v.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskSeriesSerializer {
+    pub id: i32,
+    pub frequency: RecurrenceFrequency,
+    pub interval: i32,
+    pub by_weekday: Vec<String>,
+    pub starts_at: String,
+    pub until: Option<String>,
+    pub count: Option<i32>,
+    pub occurrence_count: i32,
+    pub active: bool,
+}
+
+impl From<task_series::Model> for TaskSeriesSerializer {
+    fn from(value: task_series::Model) -> Self {
+        Self {
+            id: value.id,
+            frequency: value.frequency,
+            interval: value.interval,
+            by_weekday: value
+                .by_weekday
+                .map(|by_weekday| by_weekday.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            starts_at: value.starts_at.to_string(),
+            until: value.until.map(|v| v.to_string()),
+            count: value.count,
+            occurrence_count: value.occurrence_count,
+            active: value.active,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskDependenciesSerializer {
+    /// Tasks that have to be done before this one can start.
+    pub blocked_by: Vec<TaskSerializer>,
+    /// Tasks waiting on this one.
+    pub blocks: Vec<TaskSerializer>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskTreeSerializer {
+    #[serde(flatten)]
+    pub task: TaskSerializer,
+    /// 100 or 0 for a task without subtasks, otherwise the average of its subtasks.
+    pub completion: u8,
+    pub subtasks: Vec<TaskTreeSerializer>,
+}
+
+impl TaskTreeSerializer {
+    /// Builds the tree below `task` out of a flat list of its descendants.
+    pub fn build(task: task::Model, descendants: Vec<task::Model>) -> Self {
+        let mut children_by_parent: HashMap<i32, Vec<task::Model>> = HashMap::new();
+
+        for descendant in descendants {
+            if let Some(parent_id) = descendant.parent_id {
+                children_by_parent
+                    .entry(parent_id)
+                    .or_default()
+                    .push(descendant);
+            }
+        }
+
+        let (tree, _) = Self::build_node(task, &mut children_by_parent);
+
+        tree
+    }
+
+    fn build_node(
+        task: task::Model,
+        children_by_parent: &mut HashMap<i32, Vec<task::Model>>,
+    ) -> (Self, f64) {
+        let children = children_by_parent.remove(&task.id).unwrap_or_default();
+
+        let (subtasks, completions): (Vec<_>, Vec<_>) = children
+            .into_iter()
+            .map(|child| Self::build_node(child, children_by_parent))
+            .unzip();
+
+        let completion = if completions.is_empty() {
+            if task.status == TaskStatus::Done {
+                100.0
+            } else {
+                0.0
+            }
+        } else {
+            completions.iter().sum::<f64>() / completions.len() as f64
+        };
+
+        let tree = Self {
+            task: TaskSerializer::from(task),
+            completion: completion.round() as u8,
+            subtasks,
+        };
+
+        (tree, completion)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -120,3 +401,47 @@ impl From<label::Model> for LabelSerializer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i32, parent_id: Option<i32>, status: TaskStatus) -> task::Model {
+        task::Model {
+            id,
+            title: format!("task {}", id),
+            description: String::new(),
+            status,
+            priority: TaskPriority::Low,
+            uuid: id.to_string(),
+            due_date: None,
+            date_created: chrono::Utc::now().into(),
+            date_updated: None,
+            user_id: 1,
+            parent_id,
+            series_id: None,
+            comment_count: 0,
+        }
+    }
+
+    #[test]
+    fn completion_rolls_up_through_the_tree() {
+        let tree = TaskTreeSerializer::build(
+            task(1, None, TaskStatus::Pending),
+            vec![
+                task(2, Some(1), TaskStatus::Done),
+                task(3, Some(1), TaskStatus::InProgress),
+                task(4, Some(3), TaskStatus::Done),
+                task(5, Some(3), TaskStatus::Pending),
+                task(6, Some(3), TaskStatus::Pending),
+                task(7, Some(3), TaskStatus::Pending),
+            ],
+        );
+
+        assert_eq!(tree.subtasks.len(), 2);
+        assert_eq!(tree.subtasks[0].completion, 100);
+        assert_eq!(tree.subtasks[1].completion, 25);
+        assert_eq!(tree.subtasks[1].subtasks.len(), 4);
+        assert_eq!(tree.completion, 63);
+    }
+}
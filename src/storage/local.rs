@@ -0,0 +1,113 @@
+use std::{
+    io::{self, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use super::{ByteRange, ByteStream, Storage};
+
+/// Keeps objects as files below `root`, one file per key.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Keys are generated by the app, but make sure none can point outside `root`.
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let key = Path::new(key);
+
+        if key
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid storage key {}", key.display()),
+            ));
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Bytes) -> io::Result<()> {
+        let path = self.path(key)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // write to a temporary file first so readers never see a partial object
+        let partial_path = path.with_extension("part");
+        tokio::fs::write(&partial_path, &data).await?;
+        tokio::fs::rename(&partial_path, &path).await
+    }
+
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> io::Result<ByteStream> {
+        let mut file = tokio::fs::File::open(self.path(key)?).await?;
+
+        let Some(range) = range else {
+            return Ok(Box::pin(ReaderStream::new(file)));
+        };
+
+        file.seek(SeekFrom::Start(range.start)).await?;
+
+        Ok(Box::pin(ReaderStream::new(file.take(range.len()))))
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn put_get_and_delete() {
+        let root = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let storage = LocalStorage::new(&root);
+
+        storage
+            .put("attachments/1/file", Bytes::from_static(b"hello world"))
+            .await
+            .unwrap();
+
+        let range = ByteRange { start: 6, end: 10 };
+        let chunks: Vec<Bytes> = storage
+            .get("attachments/1/file", Some(range))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.concat(), b"world");
+
+        assert!(storage.get("../outside", None).await.is_err());
+
+        storage.delete("attachments/1/file").await.unwrap();
+        storage.delete("attachments/1/file").await.unwrap();
+
+        let missing = storage.get("attachments/1/file", None).await.err().unwrap();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+
+        tokio::fs::remove_dir_all(root).await.unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fmt::Debug, io, pin::Pin, sync::Arc};
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::Stream;
+
+use crate::config::{StorageBackend, StorageConfig};
+
+pub mod local;
+pub mod s3;
+
+pub type ByteStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// Where uploaded files are kept. Objects are addressed by keys like
+/// `attachments/{user_id}/{uuid}`, the metadata lives in the database.
+#[async_trait]
+pub trait Storage: Debug + Send + Sync {
+    async fn put(&self, key: &str, data: Bytes) -> io::Result<()>;
+
+    /// Streams the object, or only `range` of it. A missing object is an
+    /// `io::ErrorKind::NotFound` error.
+    async fn get(&self, key: &str, range: Option<ByteRange>) -> io::Result<ByteStream>;
+
+    /// Deleting an object that doesn't exist is not an error.
+    async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Picks the backend from `storage.backend`.
+pub fn from_config(config: &StorageConfig) -> Arc<dyn Storage> {
+    match config.backend {
+        StorageBackend::Local => Arc::new(local::LocalStorage::new(&config.path)),
+        StorageBackend::S3 => Arc::new(s3::S3Storage::from_config(&config.s3)),
+    }
+}
+
+/// An inclusive byte range, as in `Range: bytes=0-499`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RangeNotSatisfiable;
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Resolves a `Range` header against an object of `size` bytes.
+    ///
+    /// Only a single range is supported, anything else (multiple ranges, other units,
+    /// malformed values) is ignored and the whole object is served, as RFC 9110 allows.
+    pub fn parse(header: &str, size: u64) -> Result<Option<Self>, RangeNotSatisfiable> {
+        let Some(spec) = header.trim().strip_prefix("bytes=") else {
+            return Ok(None);
+        };
+
+        if spec.contains(',') {
+            return Ok(None);
+        }
+
+        let Some((start, end)) = spec.trim().split_once('-') else {
+            return Ok(None);
+        };
+
+        let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+            // bytes=-500, the last 500 bytes
+            (Err(_), Ok(suffix)) if start.is_empty() => {
+                if suffix == 0 || size == 0 {
+                    return Err(RangeNotSatisfiable);
+                }
+
+                Self {
+                    start: size.saturating_sub(suffix),
+                    end: size - 1,
+                }
+            }
+            // bytes=500-
+            (Ok(start), Err(_)) if end.is_empty() => {
+                if start >= size {
+                    return Err(RangeNotSatisfiable);
+                }
+
+                Self {
+                    start,
+                    end: size - 1,
+                }
+            }
+            (Ok(start), Ok(end)) if start <= end => {
+                if start >= size {
+                    return Err(RangeNotSatisfiable);
+                }
+
+                Self {
+                    start,
+                    end: end.min(size - 1),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> Result<Option<ByteRange>, RangeNotSatisfiable> {
+        Ok(Some(ByteRange { start, end }))
+    }
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(ByteRange::parse("bytes=0-9", 100), range(0, 9));
+        assert_eq!(ByteRange::parse("bytes=90-", 100), range(90, 99));
+        assert_eq!(ByteRange::parse("bytes=-10", 100), range(90, 99));
+        assert_eq!(ByteRange::parse("bytes=50-500", 100), range(50, 99));
+        assert_eq!(ByteRange::parse("bytes=-500", 100), range(0, 99));
+    }
+
+    #[test]
+    fn ignores_or_rejects_other_ranges() {
+        assert_eq!(ByteRange::parse("bytes=0-1,5-6", 100), Ok(None));
+        assert_eq!(ByteRange::parse("items=0-1", 100), Ok(None));
+        assert_eq!(ByteRange::parse("bytes=9-0", 100), Ok(None));
+        assert_eq!(ByteRange::parse("bytes=abc", 100), Ok(None));
+
+        assert_eq!(
+            ByteRange::parse("bytes=100-", 100),
+            Err(RangeNotSatisfiable)
+        );
+        assert_eq!(ByteRange::parse("bytes=-0", 100), Err(RangeNotSatisfiable));
+        assert_eq!(ByteRange::parse("bytes=0-0", 0), Err(RangeNotSatisfiable));
+    }
+}
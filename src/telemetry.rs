@@ -0,0 +1,122 @@
+use std::sync::OnceLock;
+
+use metrics::{gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sea_orm::{
+    ActiveEnum, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait,
+    Iterable, QuerySelect,
+};
+
+use crate::models::_entities::{sea_orm_active_enums::TaskStatus, task};
+
+/// Buckets of every `*_seconds` histogram, from 1ms to 10s.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static RECORDER: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Handle of the global Prometheus recorder, installed on first use since there can
+/// only be one per process.
+pub fn recorder() -> PrometheusHandle {
+    RECORDER
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), &LATENCY_BUCKETS)
+                .expect("Latency buckets are not empty")
+                .install_recorder()
+                .expect("Cannot install the metrics recorder")
+        })
+        .clone()
+}
+
+/// Records the duration of every query run on `db`, labelled by its kind.
+pub fn instrument_database(db: &mut DatabaseConnection) {
+    db.set_metric_callback(|info| {
+        histogram!(
+            "db_query_duration_seconds",
+            "operation" => query_operation(&info.statement.sql),
+            "failed" => info.failed.to_string(),
+        )
+        .record(info.elapsed.as_secs_f64());
+    });
+}
+
+/// Gauges read from the pool and the database itself, refreshed on every scrape.
+pub async fn record_gauges(db: &DatabaseConnection, max_connections: u32) -> Result<(), DbErr> {
+    let pool = match db.get_database_backend() {
+        DbBackend::Postgres => {
+            let pool = db.get_postgres_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        DbBackend::Sqlite => {
+            let pool = db.get_sqlite_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        DbBackend::MySql => None,
+    };
+
+    if let Some((size, idle)) = pool {
+        let idle = idle as u32;
+
+        gauge!("db_pool_connections", "state" => "idle").set(idle);
+        gauge!("db_pool_connections", "state" => "in_use").set(size.saturating_sub(idle));
+        gauge!("db_pool_max_connections").set(max_connections);
+    }
+
+    let task_counts: Vec<(TaskStatus, i64)> = task::Entity::find()
+        .select_only()
+        .column(task::Column::Status)
+        .column_as(task::Column::Id.count(), "count")
+        .group_by(task::Column::Status)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    // statuses without tasks are reported too, they'd keep their last value otherwise
+    for status in TaskStatus::iter() {
+        let count = task_counts
+            .iter()
+            .find(|(task_status, _)| *task_status == status)
+            .map_or(0, |(_, count)| *count);
+
+        gauge!("tasks", "status" => status.to_value()).set(count as f64);
+    }
+
+    Ok(())
+}
+
+fn query_operation(sql: &str) -> &'static str {
+    let keyword = sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match keyword.as_str() {
+        "select" => "select",
+        "insert" => "insert",
+        "update" => "update",
+        "delete" => "delete",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_labelled_by_their_kind() {
+        assert_eq!(
+            query_operation("SELECT \"task\".\"id\" FROM \"task\""),
+            "select"
+        );
+        assert_eq!(
+            query_operation("  insert INTO \"task\" VALUES ($1)"),
+            "insert"
+        );
+        assert_eq!(query_operation("BEGIN"), "other");
+        assert_eq!(query_operation(""), "other");
+    }
+}
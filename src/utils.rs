@@ -1,54 +1,49 @@
 use std::sync::Arc;
 
-use hmac::{self, Hmac, Mac};
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use sea_orm::ColumnTrait;
-use sea_orm::{EntityTrait, QueryFilter};
-use sha2::Sha256;
+use sea_orm::EntityTrait;
 
 use crate::AppState;
-use crate::{auth::jwt::TokenClaims, error::AppError, models::_entities::user};
-
-pub fn hash(text: &str) -> String {
-    let mut mac: Hmac<Sha256> =
-        Hmac::new_from_slice(b"secret_key").expect("HMAC can take key of any size");
-
-    mac.update(text.as_bytes());
-
-    let result = mac.finalize();
-    let code_bytes = result.into_bytes();
-
-    hex::encode(code_bytes)
-}
-
-pub fn verify_password(hex_code: &str, to_verify: &str) -> Result<bool, AppError> {
-    let mut mac: Hmac<Sha256> =
-        Hmac::new_from_slice(b"secret_key").expect("HMAC can take key of any size");
-
-    mac.update(to_verify.as_bytes());
-
-    let result = mac.finalize();
-
-    let code_byte = hex::decode(hex_code).map_err(|err| AppError::GenericError(err.to_string()))?;
-
-    Ok(code_byte[..] == result.into_bytes()[..])
-}
-
-pub async fn verify_token(app_state: Arc<AppState>, token: &str) -> Result<user::Model, AppError> {
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT Secret not set.");
+use crate::{
+    auth::{
+        jwt::{decode_user_token, TokenClaims, TokenType},
+        revocation::is_cut_off,
+    },
+    error::AppError,
+    models::_entities::user,
+};
+
+pub async fn verify_token(
+    app_state: Arc<AppState>,
+    token: &str,
+) -> Result<(user::Model, TokenClaims), AppError> {
+    let token_claims =
+        decode_user_token(token, TokenType::Access, &app_state.config.auth.jwt_secret)?;
+
+    if app_state
+        .revoked_tokens
+        .is_revoked(&app_state.db, &token_claims.jti)
+        .await?
+    {
+        return Err(AppError::Unauthorized(
+            "Authentication credentials are invalid.".to_string(),
+        ));
+    }
 
-    let token_claim = decode::<TokenClaims>(
-        token,
-        &DecodingKey::from_secret(jwt_secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| AppError::Unauthorized("Authentication credentials are invalid.".to_string()))?;
+    let user_id: i32 = token_claims.sub.parse().map_err(|_| {
+        AppError::Unauthorized("Authentication credentials are invalid.".to_string())
+    })?;
 
-    let user = user::Entity::find()
-        .filter(user::Column::Email.eq(token_claim.claims.sub))
+    let user = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
         .ok_or(sea_orm::DbErr::RecordNotFound("User not found.".into()))?;
 
-    Ok(user)
+    // set by "log out everywhere" and password changes
+    if is_cut_off(&token_claims, user.token_valid_after) {
+        return Err(AppError::Unauthorized(
+            "Authentication credentials are invalid.".to_string(),
+        ));
+    }
+
+    Ok((user, token_claims))
 }
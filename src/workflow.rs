@@ -0,0 +1,107 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::ActiveEnum;
+
+use crate::{
+    config::WorkflowConfig, error::AppError, models::_entities::sea_orm_active_enums::TaskStatus,
+};
+
+/// Which status changes a task is allowed to go through.
+#[derive(Debug, Clone)]
+pub struct TaskWorkflow {
+    transitions: HashMap<TaskStatus, HashSet<TaskStatus>>,
+}
+
+impl TaskWorkflow {
+    pub fn new(transitions: impl IntoIterator<Item = (TaskStatus, TaskStatus)>) -> Self {
+        let mut workflow = Self {
+            transitions: HashMap::new(),
+        };
+
+        for (from, to) in transitions {
+            workflow.transitions.entry(from).or_default().insert(to);
+        }
+
+        workflow
+    }
+
+    /// Keeping the current status is always allowed.
+    pub fn can_transition(&self, from: TaskStatus, to: TaskStatus) -> bool {
+        from == to
+            || self
+                .transitions
+                .get(&from)
+                .is_some_and(|allowed| allowed.contains(&to))
+    }
+
+    pub fn check_transition(&self, from: TaskStatus, to: TaskStatus) -> Result<(), AppError> {
+        if self.can_transition(from, to) {
+            return Ok(());
+        }
+
+        let mut allowed: Vec<String> = self
+            .transitions
+            .get(&from)
+            .into_iter()
+            .flatten()
+            .map(|status| status.to_value())
+            .collect();
+        allowed.sort();
+
+        Err(AppError::Conflict(format!(
+            "Cannot change status from '{}' to '{}'. Allowed: {}.",
+            from.to_value(),
+            to.to_value(),
+            if allowed.is_empty() {
+                "none".to_string()
+            } else {
+                allowed.join(", ")
+            }
+        )))
+    }
+}
+
+impl From<&WorkflowConfig> for TaskWorkflow {
+    fn from(config: &WorkflowConfig) -> Self {
+        Self::new(
+            config
+                .transitions
+                .iter()
+                .flat_map(|(from, targets)| targets.iter().map(move |to| (*from, *to))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TaskStatus::*;
+
+    #[test]
+    fn default_workflow() {
+        let workflow = TaskWorkflow::from(&WorkflowConfig::default());
+
+        assert!(workflow.can_transition(Pending, InProgress));
+        assert!(workflow.can_transition(InProgress, Done));
+        assert!(workflow.can_transition(Done, Reopened));
+        assert!(workflow.can_transition(Done, Done));
+
+        assert!(!workflow.can_transition(Done, InProgress));
+        assert!(!workflow.can_transition(Pending, Reopened));
+    }
+
+    #[test]
+    fn illegal_transition_is_a_conflict() {
+        let workflow = TaskWorkflow::new([(Pending, Done)]);
+
+        match workflow.check_transition(Done, Pending) {
+            Err(AppError::Conflict(message)) => {
+                assert_eq!(
+                    message,
+                    "Cannot change status from 'done' to 'pending'. Allowed: none."
+                )
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+}